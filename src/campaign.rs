@@ -0,0 +1,355 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::{
+    GameState, MAGICAL_BLUE,
+    level::{CurrentLevel, LevelEntity},
+    score::{LevelScore, spawn_stars},
+};
+
+/*
+========================================================================================
+Campaign
+
+An ordered list of levels; completing a level unlocks the next one
+The level select screen shows all levels of the campaign as buttons (no text, the order tells the story)
+completed levels show the best star rating the player has reached
+========================================================================================
+*/
+
+// levels in the order they are played
+pub const CAMPAIGN_LEVELS: [&str; 2] =
+    ["levels/01_first_steps.level.ron", "levels/02_mvp.level.ron"];
+
+// time to admire the donut before the next level starts
+const LEVEL_TRANSITION_SECONDS: f32 = 3.0;
+
+const LEVEL_BUTTON_IMAGE: &str = "UI Pack/PNG/Blue/Double/button_rectangle_depth_line.png";
+
+const LOCKED_LEVEL_GREY: Color = Color::LinearRgba(LinearRgba::rgb(0.3, 0.3, 0.3));
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CampaignProgress>()
+            .add_systems(OnEnter(GameState::MainMenu), show_level_select_screen)
+            .add_systems(OnExit(GameState::MainMenu), hide_level_select_screen)
+            .add_systems(OnEnter(GameState::Won), complete_level)
+            .add_systems(OnEnter(GameState::Lost), retry_level)
+            .add_systems(
+                Update,
+                (
+                    handle_level_select_buttons.run_if(in_state(GameState::MainMenu)),
+                    advance_level_transition
+                        .run_if(in_state(GameState::Won).or(in_state(GameState::Lost))),
+                ),
+            );
+    }
+}
+
+/*
+Stores which levels of the campaign have been completed and their best score, by level path
+ */
+#[derive(Resource, Default)]
+pub struct CampaignProgress {
+    pub completed_levels: HashMap<String, LevelScore>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum LevelStatus {
+    Locked,
+    Unlocked,
+    Completed,
+}
+
+impl CampaignProgress {
+    /*
+    The first level is always unlocked, every other level is unlocked by completing the one before
+     */
+    pub fn level_status(&self, campaign_index: usize) -> LevelStatus {
+        if self
+            .completed_levels
+            .contains_key(CAMPAIGN_LEVELS[campaign_index])
+        {
+            LevelStatus::Completed
+        } else if campaign_index == 0
+            || self
+                .completed_levels
+                .contains_key(CAMPAIGN_LEVELS[campaign_index - 1])
+        {
+            LevelStatus::Unlocked
+        } else {
+            LevelStatus::Locked
+        }
+    }
+}
+
+/*
+Counts down after a level was won (or lost) and then starts the next level (or returns to the level select screen)
+ */
+#[derive(Resource)]
+struct LevelTransition {
+    timer: Timer,
+    next_level: Option<usize>,
+}
+
+/*
+Marks the root UI node of the level select screen
+ */
+#[derive(Component)]
+struct LevelSelectScreen;
+
+#[derive(Component)]
+struct LevelSelectButton {
+    campaign_index: usize,
+}
+
+/*
+Spawns the level select screen whenever the player is sent to it
+ */
+fn show_level_select_screen(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    campaign_progress: Res<CampaignProgress>,
+) {
+    commands
+        .spawn((
+            LevelSelectScreen,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(24.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for (campaign_index, level_path) in CAMPAIGN_LEVELS.iter().enumerate() {
+                let level_status = campaign_progress.level_status(campaign_index);
+
+                let button_color = match level_status {
+                    LevelStatus::Locked => LOCKED_LEVEL_GREY,
+                    LevelStatus::Unlocked => Color::WHITE,
+                    LevelStatus::Completed => MAGICAL_BLUE,
+                };
+
+                let mut button = parent.spawn((
+                    LevelSelectButton { campaign_index },
+                    ImageNode::new(asset_server.load(LEVEL_BUTTON_IMAGE)).with_color(button_color),
+                    Node {
+                        width: Val::Px(192.0),
+                        height: Val::Px(64.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                ));
+
+                // locked levels are no buttons at all, so they can't be clicked
+                if level_status != LevelStatus::Locked {
+                    button.insert(Button);
+                }
+
+                if let Some(best_score) = campaign_progress.completed_levels.get(*level_path) {
+                    button.with_children(|button| {
+                        spawn_stars(button, &asset_server, best_score.stars, 48.0);
+                    });
+                }
+            }
+        });
+}
+
+/*
+Removes the level select screen once a level was chosen
+ */
+fn hide_level_select_screen(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    level_select_screen: Query<Entity, With<LevelSelectScreen>>,
+) {
+    for screen in level_select_screen {
+        commands.entity(screen).despawn();
+    }
+}
+
+/*
+Starts the level whose button was pressed
+ */
+fn handle_level_select_buttons(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    // Queries
+    level_select_buttons: Query<(&Interaction, &LevelSelectButton), Changed<Interaction>>,
+    level_entities: Query<Entity, (With<LevelEntity>, Without<ChildOf>)>,
+) {
+    for (interaction, level_select_button) in level_select_buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        start_level(
+            &mut commands,
+            &asset_server,
+            &mut next_game_state,
+            &level_entities,
+            level_select_button.campaign_index,
+        );
+
+        break;
+    }
+}
+
+/*
+Marks the current level as completed, keeps its best score and schedules the transition to the next level
+ */
+fn complete_level(
+    // Globals
+    mut commands: Commands,
+    mut campaign_progress: ResMut<CampaignProgress>,
+    current_level: Option<Res<CurrentLevel>>,
+    level_score: Option<Res<LevelScore>>,
+) {
+    let Some(current_level) = current_level else {
+        warn!("Level completed, but no level is running");
+        return;
+    };
+
+    // test runs of the level editor go back to the editor instead
+    let Some(campaign_index) = current_level.campaign_index else {
+        return;
+    };
+
+    // note: a level that was won without a score (should not happen) still counts as completed
+    let level_score = level_score
+        .map(|level_score| *level_score)
+        .unwrap_or_default();
+
+    let best_score = campaign_progress
+        .completed_levels
+        .entry(CAMPAIGN_LEVELS[campaign_index].to_string())
+        .or_insert(level_score);
+
+    if level_score.score > best_score.score {
+        *best_score = level_score;
+    }
+
+    let next_level = campaign_index + 1;
+
+    commands.insert_resource(LevelTransition {
+        timer: Timer::from_seconds(LEVEL_TRANSITION_SECONDS, TimerMode::Once),
+        next_level: (next_level < CAMPAIGN_LEVELS.len()).then_some(next_level),
+    });
+
+    info!(
+        "Level {} completed with {} stars (best: {})",
+        campaign_index + 1,
+        level_score.stars,
+        best_score.stars
+    );
+}
+
+/*
+Schedules a restart of the current level after all balls were used up
+ */
+fn retry_level(
+    // Globals
+    mut commands: Commands,
+    current_level: Option<Res<CurrentLevel>>,
+) {
+    let Some(current_level) = current_level else {
+        warn!("Level lost, but no level is running");
+        return;
+    };
+
+    let Some(campaign_index) = current_level.campaign_index else {
+        return;
+    };
+
+    commands.insert_resource(LevelTransition {
+        timer: Timer::from_seconds(LEVEL_TRANSITION_SECONDS, TimerMode::Once),
+        next_level: Some(campaign_index),
+    });
+
+    info!("Level {} lost, retrying", campaign_index + 1);
+}
+
+/*
+Starts the next level once the transition timer has finished; after the last level the player returns to the level select screen
+ */
+fn advance_level_transition(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    level_transition: Option<ResMut<LevelTransition>>,
+    // Queries
+    level_entities: Query<Entity, (With<LevelEntity>, Without<ChildOf>)>,
+) {
+    let Some(mut level_transition) = level_transition else {
+        return;
+    };
+
+    if !level_transition.timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    commands.remove_resource::<LevelTransition>();
+
+    if let Some(next_level) = level_transition.next_level {
+        start_level(
+            &mut commands,
+            &asset_server,
+            &mut next_game_state,
+            &level_entities,
+            next_level,
+        );
+    } else {
+        info!("Campaign completed, Congratulations!");
+
+        despawn_level(&mut commands, &level_entities);
+        commands.remove_resource::<CurrentLevel>();
+        next_game_state.set(GameState::MainMenu);
+    }
+}
+
+/*
+Removes the current level (if there is one) and requests the given level to be loaded
+the player can start building right away
+ */
+pub fn start_level(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    next_game_state: &mut NextState<GameState>,
+    level_entities: &Query<Entity, (With<LevelEntity>, Without<ChildOf>)>,
+    campaign_index: usize,
+) {
+    despawn_level(commands, level_entities);
+
+    commands.insert_resource(CurrentLevel {
+        campaign_index: Some(campaign_index),
+        handle: asset_server.load(CAMPAIGN_LEVELS[campaign_index]),
+        spawned: false,
+    });
+
+    next_game_state.set(GameState::Building);
+
+    info!("Starting level {}", campaign_index + 1);
+}
+
+/*
+despawns all root entities of a level; children (rune slots, placed runes) are despawned with their parents
+ */
+fn despawn_level(
+    commands: &mut Commands,
+    level_entities: &Query<Entity, (With<LevelEntity>, Without<ChildOf>)>,
+) {
+    for level_entity in level_entities {
+        commands.entity(level_entity).despawn();
+    }
+}
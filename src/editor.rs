@@ -0,0 +1,1168 @@
+use bevy::{input::mouse::MouseWheel, platform::collections::HashMap, prelude::*};
+
+use crate::{
+    ColliderType, GameState, MAGICAL_BLUE, Rune, RuneEffect, RuneEffectType, RuneGravity,
+    level::{
+        CurrentLevel, LevelColliderDefinition, LevelDefinition, LevelEntity, LevelEntityDefinition,
+        LevelEntityId, LevelEntityIndex, LevelEntityKind, LevelSpriteDefinition, spawn_level,
+    },
+    sprite_contains_point,
+};
+
+/*
+========================================================================================
+Level editor
+
+F2 on the level select screen opens the level editor; levels are built with the mouse instead of coordinates
+- the edited level is a LevelDefinition, every change respawns it -> what the editor shows is exactly what the level file says
+- 1 to 7 pick what a left click on empty space places (cards, letters, rune slots, trigger stars, ball firing thingies,
+  the donut circle, runes); rune slots are placed onto the card or letter that was clicked
+- left click drags, right click deletes, the mouse wheel rotates, Tab changes the look of cards and letters or the effect of runes
+- C on a trigger star starts a connection, a click on another entity adds it to (or removes it from) the follow ups
+- + and - change the number of test balls, F adds or removes the final ball
+- F5 test-runs the level right away (and returns to the editor), Ctrl+S saves it as a level file
+The level file is assets/levels/custom.level.ron (`--edit <file>` edits another one); in the browser it is kept in localStorage
+========================================================================================
+*/
+
+const EDITOR_LEVEL_FILE_NAME: &str = "custom.level.ron";
+#[cfg(not(target_arch = "wasm32"))]
+const EDITOR_LEVEL_DIRECTORY: &str = "assets/levels";
+
+// in degrees per mouse wheel step
+const EDITOR_ROTATION_STEP: f32 = 15.0;
+
+// balls are lined up from the right, the final ball last (same layout as the campaign levels)
+const LAST_BALL_POSITION: Vec2 = Vec2::new(550.0, -296.0);
+const BALL_SPACING: f32 = 100.0;
+const MAX_TEST_BALLS: usize = 5;
+
+// time to see the result of a test run before returning to the editor
+const TEST_RUN_RETURN_SECONDS: f32 = 2.0;
+
+const RUNE_LABEL_OFFSET: Vec3 = Vec3::new(0.0, -44.0, 1.0);
+const RUNE_LABEL_FONT_SIZE: f32 = 14.0;
+const EDITOR_STATUS_FONT_SIZE: f32 = 14.0;
+
+const CONNECTION_GOLD: Color = Color::LinearRgba(LinearRgba::rgb(1.0, 0.8, 0.2));
+
+const CARD_IMAGES: [&str; 5] = [
+    "Boardgame Pack/PNG/Cards/cardHeartsA.png",
+    "Boardgame Pack/PNG/Cards/cardHearts10.png",
+    "Boardgame Pack/PNG/Cards/cardHeartsJ.png",
+    "Boardgame Pack/PNG/Cards/cardHeartsQ.png",
+    "Boardgame Pack/PNG/Cards/cardHeartsK.png",
+];
+const LETTER_IMAGES: [&str; 5] = [
+    "letters/Letter Tiles Redux/PNG/StyleH/tileD.png",
+    "letters/Letter Tiles Redux/PNG/StyleH/tileO.png",
+    "letters/Letter Tiles Redux/PNG/StyleH/tileN.png",
+    "letters/Letter Tiles Redux/PNG/StyleH/tileU.png",
+    "letters/Letter Tiles Redux/PNG/StyleH/tileT.png",
+];
+const LETTER_SIZE: Vec2 = Vec2::new(100.0, 100.0);
+const RUNE_IMAGE: &str = "runes/PNG/Grey/Slab/runeGrey_slab_001.png";
+const RUNE_SLOT_IMAGE: &str = "runes/PNG/Black/Slab/runeBlack_slab_036.png";
+const STAR_TRIGGER_IMAGE: &str = "UI Pack/PNG/Grey/Double/star_outline.png";
+const STAR_TRIGGER_SIZE: Vec2 = Vec2::new(60.0, 60.0);
+const BALL_FIRING_THINGY_IMAGE: &str = "UI Pack/PNG/Blue/Double/check_round_round_circle.png";
+const BALL_FIRING_THINGY_ARROW_IMAGE: &str = "UI Pack/PNG/Grey/Double/arrow_decorative_w.png";
+const BALL_FIRING_SPEED: f32 = 200.0;
+const DONUT_CIRCLE_IMAGE: &str = "Particle Pack/PNG (Transparent)/magic_02.png";
+const DONUT_CIRCLE_SIZE: Vec2 = Vec2::new(200.0, 200.0);
+const BALL_IMAGE: &str = "Puzzle Assets/PNG/Double/ballGrey.png";
+
+// order in which Tab cycles through rune effects; teleport runes need a target, they are set up in the level file
+const RUNE_EFFECT_TYPES: [RuneEffectType; 9] = [
+    RuneEffectType::MoveUp,
+    RuneEffectType::MoveRight,
+    RuneEffectType::MoveDown,
+    RuneEffectType::MoveLeft,
+    RuneEffectType::Rotate,
+    RuneEffectType::Spin,
+    RuneEffectType::Scale,
+    RuneEffectType::Gravity,
+    RuneEffectType::Impulse,
+];
+
+const EDITOR_TOOL_KEYS: [(KeyCode, EditorTool); 7] = [
+    (KeyCode::Digit1, EditorTool::Card),
+    (KeyCode::Digit2, EditorTool::Letter),
+    (KeyCode::Digit3, EditorTool::RuneSlot),
+    (KeyCode::Digit4, EditorTool::StarTrigger),
+    (KeyCode::Digit5, EditorTool::BallFiringThingy),
+    (KeyCode::Digit6, EditorTool::DonutCircle),
+    (KeyCode::Digit7, EditorTool::Rune),
+];
+
+const EDITOR_HELP: &str = "1 card  2 letter  3 rune slot  4 trigger star  5 ball firing thingy  6 donut circle  7 rune\n\
+    left click: place / drag   right click: delete   wheel: rotate   Tab: look / rune effect   M: machine part\n\
+    C: connect trigger star   +/-: test balls   F: final ball   F5: test run   Ctrl+S: save   Esc: back";
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorFile>()
+            .init_resource::<EditorTool>()
+            .init_resource::<EditorInteraction>()
+            .add_systems(OnEnter(GameState::Editing), open_editor)
+            .add_systems(OnExit(GameState::Editing), close_editor)
+            .add_systems(
+                OnEnter(GameState::Won),
+                schedule_return_to_editor.run_if(resource_exists::<EditorTestRun>),
+            )
+            .add_systems(
+                OnEnter(GameState::Lost),
+                schedule_return_to_editor.run_if(resource_exists::<EditorTestRun>),
+            )
+            .add_systems(
+                Update,
+                (
+                    handle_open_editor_key.run_if(in_state(GameState::MainMenu)),
+                    (
+                        handle_editor_keys,
+                        edit_hovered_entity,
+                        handle_editor_clicks,
+                        drag_edited_entity,
+                        respawn_edited_level.run_if(resource_changed::<EditorLevel>),
+                        label_runes,
+                        draw_trigger_connections,
+                        update_editor_status,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Editing)),
+                    (handle_test_run_key, advance_return_to_editor)
+                        .run_if(resource_exists::<EditorTestRun>)
+                        .run_if(not(in_state(GameState::Paused))),
+                ),
+            );
+
+        #[cfg(not(target_arch = "wasm32"))]
+        edit_file_from_command_line(app);
+    }
+}
+
+/*
+Where the edited level is loaded from and saved to
+(native: path of the level file, browser: name of the file in localStorage)
+ */
+#[derive(Resource)]
+struct EditorFile {
+    path: String,
+}
+
+impl Default for EditorFile {
+    fn default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let path = format!("{}/{}", EDITOR_LEVEL_DIRECTORY, EDITOR_LEVEL_FILE_NAME);
+        #[cfg(target_arch = "wasm32")]
+        let path = EDITOR_LEVEL_FILE_NAME.to_string();
+
+        EditorFile { path }
+    }
+}
+
+/*
+The level being edited; it is kept while test-running it, so the editor continues where it was left
+ */
+#[derive(Resource)]
+struct EditorLevel {
+    definition: LevelDefinition,
+}
+
+/*
+What a left click on empty space places
+ */
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+enum EditorTool {
+    #[default]
+    Card,
+    Letter,
+    RuneSlot,
+    StarTrigger,
+    BallFiringThingy,
+    DonutCircle,
+    Rune,
+}
+
+#[derive(Resource, Default)]
+struct EditorInteraction {
+    dragged: Option<DraggedEntity>,
+    // id of the trigger star the next click connects
+    connecting_from: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+struct DraggedEntity {
+    entity: Entity,
+    index: usize,
+    // from the cursor to the center of the entity, so it doesn't jump when grabbed
+    grab_offset: Vec2,
+}
+
+/*
+Exists while the level of the editor is played; a won or lost test run returns to the editor after a moment
+ */
+#[derive(Resource)]
+struct EditorTestRun {
+    return_timer: Option<Timer>,
+}
+
+#[derive(Component)]
+struct EditorStatus;
+
+#[derive(Component)]
+struct RuneLabel;
+
+/*
+========================================================================================
+Entering & leaving the editor
+========================================================================================
+*/
+
+/*
+`--edit <file>` edits the given level file and opens the editor right away
+ */
+#[cfg(not(target_arch = "wasm32"))]
+fn edit_file_from_command_line(app: &mut App) {
+    let mut args = std::env::args().skip_while(|arg| arg != "--edit").skip(1);
+
+    let Some(path) = args.next() else {
+        return;
+    };
+
+    app.insert_resource(EditorFile { path });
+    app.world_mut()
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Editing);
+}
+
+fn handle_open_editor_key(
+    // Globals
+    input: Res<ButtonInput<KeyCode>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if input.just_pressed(KeyCode::F2) {
+        next_game_state.set(GameState::Editing);
+    }
+}
+
+/*
+Loads the level file the first time, afterwards (e.g. after a test run) the level is just shown again
+ */
+fn open_editor(
+    // Globals
+    mut commands: Commands,
+    editor_file: Res<EditorFile>,
+    editor_level: Option<ResMut<EditorLevel>>,
+) {
+    // the level of a test run is replaced by the edited one
+    commands.remove_resource::<CurrentLevel>();
+    commands.remove_resource::<EditorTestRun>();
+
+    commands.spawn((
+        EditorStatus,
+        Text::default(),
+        TextFont::from_font_size(EDITOR_STATUS_FONT_SIZE),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(240.0),
+            top: Val::Px(16.0),
+            ..default()
+        },
+    ));
+
+    if let Some(mut editor_level) = editor_level {
+        editor_level.set_changed();
+        return;
+    }
+
+    let definition = match read_level_file(&editor_file.path) {
+        Ok(Some(level_ron)) => match ron::from_str::<LevelDefinition>(&level_ron) {
+            Ok(definition) => definition,
+            Err(error) => {
+                warn!(
+                    "Could not parse {}, starting a new level: {}",
+                    editor_file.path, error
+                );
+                new_level()
+            }
+        },
+        Ok(None) => new_level(),
+        Err(error) => {
+            warn!(
+                "Could not read {}, starting a new level: {}",
+                editor_file.path, error
+            );
+            new_level()
+        }
+    };
+
+    info!("Editing {}", editor_file.path);
+
+    commands.insert_resource(EditorLevel { definition });
+}
+
+/*
+Removes the edited level, whether the editor is left or the level is test-run (which spawns it on its own)
+ */
+fn close_editor(
+    // Globals
+    mut commands: Commands,
+    mut editor_interaction: ResMut<EditorInteraction>,
+    // Queries
+    level_entities: Query<Entity, (With<LevelEntity>, Without<ChildOf>)>,
+    editor_status: Query<Entity, With<EditorStatus>>,
+) {
+    for entity in level_entities.iter().chain(editor_status.iter()) {
+        commands.entity(entity).despawn();
+    }
+
+    *editor_interaction = EditorInteraction::default();
+}
+
+/*
+A level with everything the machine needs to run: a donut circle, a ball firing thingy and balls
+ */
+fn new_level() -> LevelDefinition {
+    let mut definition = LevelDefinition {
+        trajectory_preview: true,
+        stall_detection: default(),
+        scoring: default(),
+        entities: vec![
+            EditorTool::DonutCircle.template(Vec2::new(0.0, -280.0)),
+            EditorTool::BallFiringThingy.template(Vec2::new(580.0, -100.0)),
+        ],
+    };
+    set_balls(&mut definition, 2, true);
+
+    definition
+}
+
+/*
+========================================================================================
+Editing
+========================================================================================
+*/
+
+impl EditorTool {
+    fn name(&self) -> &'static str {
+        match self {
+            EditorTool::Card => "card",
+            EditorTool::Letter => "letter",
+            EditorTool::RuneSlot => "rune slot",
+            EditorTool::StarTrigger => "trigger star",
+            EditorTool::BallFiringThingy => "ball firing thingy",
+            EditorTool::DonutCircle => "donut circle",
+            EditorTool::Rune => "rune",
+        }
+    }
+
+    /*
+    A new entity placed with this tool, looking like the ones in the campaign levels
+     */
+    fn template(&self, position: Vec2) -> LevelEntityDefinition {
+        let (kind, image, custom_size, collider) = match self {
+            EditorTool::Card => (
+                LevelEntityKind::Card { machine_part: true },
+                CARD_IMAGES[0],
+                None,
+                Some((1.0, ColliderType::Rectangle)),
+            ),
+            EditorTool::Letter => (
+                LevelEntityKind::Letter { machine_part: true },
+                LETTER_IMAGES[0],
+                Some(LETTER_SIZE),
+                Some((1.0, ColliderType::Rectangle)),
+            ),
+            EditorTool::RuneSlot => (
+                // attached by the caller
+                LevelEntityKind::RuneSlot {
+                    attached_to: String::new(),
+                    teleport_to: None,
+                },
+                RUNE_SLOT_IMAGE,
+                None,
+                Some((0.5, ColliderType::Rectangle)),
+            ),
+            EditorTool::StarTrigger => (
+                LevelEntityKind::StarTrigger {
+                    follow_up: Vec::new(),
+                },
+                STAR_TRIGGER_IMAGE,
+                Some(STAR_TRIGGER_SIZE),
+                Some((1.0, ColliderType::Rectangle)),
+            ),
+            EditorTool::BallFiringThingy => (
+                LevelEntityKind::BallFiringThingy {
+                    firing_speed: BALL_FIRING_SPEED,
+                    arrow_image: BALL_FIRING_THINGY_ARROW_IMAGE.to_string(),
+                    adjustable: None,
+                },
+                BALL_FIRING_THINGY_IMAGE,
+                None,
+                Some((0.4, ColliderType::Circle)),
+            ),
+            EditorTool::DonutCircle => (
+                LevelEntityKind::DonutCircle,
+                DONUT_CIRCLE_IMAGE,
+                Some(DONUT_CIRCLE_SIZE),
+                None,
+            ),
+            EditorTool::Rune => (
+                LevelEntityKind::Rune {
+                    effect: rune_effect(RUNE_EFFECT_TYPES[0]),
+                },
+                RUNE_IMAGE,
+                None,
+                Some((1.0, ColliderType::Rectangle)),
+            ),
+        };
+
+        LevelEntityDefinition {
+            id: None,
+            kind,
+            position,
+            rotation: 0.0,
+            sprite: Some(LevelSpriteDefinition {
+                image: image.to_string(),
+                custom_size,
+            }),
+            collider: collider.map(|(collider_scale, collider_type)| LevelColliderDefinition {
+                collider_scale,
+                collider_type,
+            }),
+        }
+    }
+}
+
+/*
+A rune effect of the given type with settings that work for most machines; fine tuning happens in the level file
+ */
+fn rune_effect(rune_effect_type: RuneEffectType) -> RuneEffect {
+    let mut rune_effect = RuneEffect {
+        rune_effect_type,
+        ..default()
+    };
+
+    match rune_effect_type {
+        RuneEffectType::MoveUp => rune_effect.rune_effect_move_speed = Some(Vec2::Y * 200.0),
+        RuneEffectType::MoveDown => rune_effect.rune_effect_move_speed = Some(Vec2::NEG_Y * 200.0),
+        RuneEffectType::MoveLeft => rune_effect.rune_effect_move_speed = Some(Vec2::NEG_X * 200.0),
+        RuneEffectType::MoveRight => rune_effect.rune_effect_move_speed = Some(Vec2::X * 200.0),
+        RuneEffectType::Rotate => {
+            rune_effect.rune_effect_rotation_speed = Some(90.0);
+            rune_effect.rune_effect_rotation_angle = Some(90.0);
+        }
+        RuneEffectType::Spin => rune_effect.rune_effect_rotation_speed = Some(90.0),
+        RuneEffectType::Scale => {
+            rune_effect.rune_effect_scale_speed = Some(0.5);
+            rune_effect.rune_effect_scale_target = Some(2.0);
+        }
+        RuneEffectType::Gravity => rune_effect.rune_effect_gravity = Some(RuneGravity::Normal),
+        RuneEffectType::Impulse => rune_effect.rune_effect_impulse = Some(Vec2::Y * 400.0),
+        RuneEffectType::Teleport => {}
+    }
+
+    rune_effect
+}
+
+/*
+Replaces all balls of the level with the given number of test balls (and the final ball)
+ */
+fn set_balls(definition: &mut LevelDefinition, test_balls: usize, final_ball: bool) {
+    definition.entities.retain(|entity| {
+        !matches!(
+            entity.kind,
+            LevelEntityKind::GreyBall | LevelEntityKind::FinalBall
+        )
+    });
+
+    let kinds = std::iter::repeat_n(LevelEntityKind::GreyBall, test_balls)
+        .chain(final_ball.then_some(LevelEntityKind::FinalBall));
+    let balls = test_balls + final_ball as usize;
+
+    for (ball, kind) in kinds.enumerate() {
+        let position = LAST_BALL_POSITION - Vec2::X * BALL_SPACING * (balls - 1 - ball) as f32;
+
+        definition.entities.push(LevelEntityDefinition {
+            id: None,
+            kind,
+            position,
+            rotation: 0.0,
+            sprite: Some(LevelSpriteDefinition {
+                image: BALL_IMAGE.to_string(),
+                custom_size: None,
+            }),
+            collider: Some(LevelColliderDefinition {
+                collider_scale: 1.0,
+                collider_type: ColliderType::Circle,
+            }),
+        });
+    }
+}
+
+/*
+Number of test balls and whether there is a final ball
+ */
+fn count_balls(definition: &LevelDefinition) -> (usize, bool) {
+    let test_balls = definition
+        .entities
+        .iter()
+        .filter(|entity| matches!(entity.kind, LevelEntityKind::GreyBall))
+        .count();
+    let final_ball = definition
+        .entities
+        .iter()
+        .any(|entity| matches!(entity.kind, LevelEntityKind::FinalBall));
+
+    (test_balls, final_ball)
+}
+
+/*
+Entities that trigger other entities
+ */
+fn follow_up_mut(kind: &mut LevelEntityKind) -> Option<&mut Vec<String>> {
+    match kind {
+        LevelEntityKind::StarTrigger { follow_up }
+        | LevelEntityKind::Gate { follow_up, .. }
+        | LevelEntityKind::Delay { follow_up, .. }
+        | LevelEntityKind::Metronome { follow_up, .. } => Some(follow_up),
+        _ => None,
+    }
+}
+
+fn follow_up(kind: &LevelEntityKind) -> Option<&Vec<String>> {
+    match kind {
+        LevelEntityKind::StarTrigger { follow_up }
+        | LevelEntityKind::Gate { follow_up, .. }
+        | LevelEntityKind::Delay { follow_up, .. }
+        | LevelEntityKind::Metronome { follow_up, .. } => Some(follow_up),
+        _ => None,
+    }
+}
+
+/*
+Used for the ids the editor generates
+ */
+fn kind_name(kind: &LevelEntityKind) -> &'static str {
+    match kind {
+        LevelEntityKind::Rune { .. } => "rune",
+        LevelEntityKind::Decoration { .. } => "decoration",
+        LevelEntityKind::GreyBall => "test_ball",
+        LevelEntityKind::FinalBall => "final_ball",
+        LevelEntityKind::BallFiringThingy { .. } => "ball_firing_thingy",
+        LevelEntityKind::DonutCircle => "donut_circle",
+        LevelEntityKind::Card { .. } => "card",
+        LevelEntityKind::Letter { .. } => "letter",
+        LevelEntityKind::RuneSlot { .. } => "rune_slot",
+        LevelEntityKind::StarTrigger { .. } => "trigger_star",
+        LevelEntityKind::Connector { .. } => "connector",
+        LevelEntityKind::Gate { .. } => "gate",
+        LevelEntityKind::Delay { .. } => "delay",
+        LevelEntityKind::Metronome { .. } => "metronome",
+        LevelEntityKind::Portal { .. } => "portal",
+    }
+}
+
+/*
+The id of the entity; entities without one get a new one (e.g. card_3), so they can be referenced
+ */
+fn ensure_id(definition: &mut LevelDefinition, index: usize) -> String {
+    if let Some(id) = &definition.entities[index].id {
+        return id.clone();
+    }
+
+    let kind_name = kind_name(&definition.entities[index].kind);
+
+    let id = (1..)
+        .map(|number| format!("{}_{}", kind_name, number))
+        .find(|id| {
+            !definition
+                .entities
+                .iter()
+                .any(|entity| entity.id.as_ref() == Some(id))
+        })
+        .unwrap();
+
+    definition.entities[index].id = Some(id.clone());
+
+    id
+}
+
+/*
+Removes the entity and everything that only exists for it: its rune slots and the connections to it
+ */
+fn delete_entity(definition: &mut LevelDefinition, index: usize) {
+    let deleted = definition.entities.remove(index);
+
+    let Some(id) = deleted.id else {
+        return;
+    };
+
+    definition.entities.retain(|entity| {
+        !matches!(&entity.kind, LevelEntityKind::RuneSlot { attached_to, .. } if *attached_to == id)
+    });
+
+    for entity in &mut definition.entities {
+        if let Some(follow_up) = follow_up_mut(&mut entity.kind) {
+            follow_up.retain(|follow_up| *follow_up != id);
+        }
+
+        if let LevelEntityKind::RuneSlot { teleport_to, .. } = &mut entity.kind
+            && teleport_to.as_ref() == Some(&id)
+        {
+            *teleport_to = None;
+        }
+    }
+}
+
+/*
+Next look of cards and letters, next effect of runes
+ */
+fn cycle_variant(entity: &mut LevelEntityDefinition) {
+    let images: &[&str] = match &mut entity.kind {
+        LevelEntityKind::Card { .. } => &CARD_IMAGES,
+        LevelEntityKind::Letter { .. } => &LETTER_IMAGES,
+        LevelEntityKind::Rune { effect } => {
+            let next = RUNE_EFFECT_TYPES
+                .iter()
+                .position(|rune_effect_type| *rune_effect_type == effect.rune_effect_type)
+                .map_or(0, |current| (current + 1) % RUNE_EFFECT_TYPES.len());
+            *effect = rune_effect(RUNE_EFFECT_TYPES[next]);
+            return;
+        }
+        _ => return,
+    };
+
+    let Some(sprite) = &mut entity.sprite else {
+        return;
+    };
+
+    let next = images
+        .iter()
+        .position(|image| *image == sprite.image)
+        .map_or(0, |current| (current + 1) % images.len());
+    sprite.image = images[next].to_string();
+}
+
+fn cursor_position_in_world(
+    windows: &Query<&Window>,
+    camera_q: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor_position = windows.single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera_q.single().ok()?;
+
+    camera
+        .viewport_to_world_2d(camera_transform, cursor_position)
+        .ok()
+}
+
+/*
+The topmost level entity under the point
+ */
+fn hovered_entity(
+    images: &Res<Assets<Image>>,
+    level_entities: &Query<(Entity, &LevelEntityIndex, &Sprite, &GlobalTransform)>,
+    point: Vec2,
+) -> Option<(Entity, usize)> {
+    level_entities
+        .iter()
+        .filter(|(_, _, sprite, global_transform)| {
+            sprite_contains_point(images, sprite, global_transform, point)
+        })
+        .max_by(|(_, _, _, a), (_, _, _, b)| a.translation().z.total_cmp(&b.translation().z))
+        .map(|(entity, index, _, _)| (entity, index.0))
+}
+
+/*
+Tools, balls, saving and leaving the editor
+ */
+fn handle_editor_keys(
+    // Globals
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut levels: ResMut<Assets<LevelDefinition>>,
+    mut editor_tool: ResMut<EditorTool>,
+    mut editor_level: ResMut<EditorLevel>,
+    editor_file: Res<EditorFile>,
+    editor_interaction: Res<EditorInteraction>,
+) {
+    if let Some((_, tool)) = EDITOR_TOOL_KEYS
+        .iter()
+        .find(|(key, _)| input.just_pressed(*key))
+    {
+        *editor_tool = *tool;
+    }
+
+    // note: a dragged entity is written back by its index once it is dropped, adding or removing balls would shift it
+    if editor_interaction.dragged.is_none() {
+        let (test_balls, final_ball) = count_balls(&editor_level.definition);
+
+        if input.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd])
+            && test_balls < MAX_TEST_BALLS
+        {
+            set_balls(&mut editor_level.definition, test_balls + 1, final_ball);
+        }
+
+        if input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) && test_balls > 0 {
+            set_balls(&mut editor_level.definition, test_balls - 1, final_ball);
+        }
+
+        if input.just_pressed(KeyCode::KeyF) {
+            set_balls(&mut editor_level.definition, test_balls, !final_ball);
+        }
+    }
+
+    if input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && input.just_pressed(KeyCode::KeyS)
+    {
+        save_level(&editor_level.definition, &editor_file.path);
+    }
+
+    if input.just_pressed(KeyCode::F5) {
+        commands.insert_resource(CurrentLevel {
+            campaign_index: None,
+            handle: levels.add(editor_level.definition.clone()),
+            spawned: false,
+        });
+        commands.insert_resource(EditorTestRun { return_timer: None });
+        next_game_state.set(GameState::Building);
+
+        info!("Test-running the edited level");
+    } else if input.just_pressed(KeyCode::Escape) {
+        next_game_state.set(GameState::MainMenu);
+    }
+}
+
+/*
+Changes to the entity under the cursor: delete, rotate, change look, machine part, start a connection
+ */
+fn edit_hovered_entity(
+    // Globals
+    input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    images: Res<Assets<Image>>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut mouse_wheel_evr: EventReader<MouseWheel>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut editor_interaction: ResMut<EditorInteraction>,
+    // Queries
+    level_entities: Query<(Entity, &LevelEntityIndex, &Sprite, &GlobalTransform)>,
+) {
+    let wheel_steps: f32 = mouse_wheel_evr
+        .read()
+        .map(|mouse_wheel| mouse_wheel.y.signum())
+        .sum();
+
+    if editor_interaction.dragged.is_some() {
+        return;
+    }
+
+    let Some(cursor_position) = cursor_position_in_world(&windows, &camera_q) else {
+        return;
+    };
+
+    if mouse_input.just_pressed(MouseButton::Right) && editor_interaction.connecting_from.is_some()
+    {
+        editor_interaction.connecting_from = None;
+        return;
+    }
+
+    let Some((_, index)) = hovered_entity(&images, &level_entities, cursor_position) else {
+        return;
+    };
+
+    if mouse_input.just_pressed(MouseButton::Right) {
+        delete_entity(&mut editor_level.definition, index);
+        return;
+    }
+
+    if wheel_steps != 0.0 {
+        let entity = &mut editor_level.definition.entities[index];
+        entity.rotation = (entity.rotation + wheel_steps * EDITOR_ROTATION_STEP).rem_euclid(360.0);
+    }
+
+    if input.just_pressed(KeyCode::Tab) {
+        cycle_variant(&mut editor_level.definition.entities[index]);
+    }
+
+    if input.just_pressed(KeyCode::KeyM) {
+        match &mut editor_level.definition.entities[index].kind {
+            LevelEntityKind::Card { machine_part } | LevelEntityKind::Letter { machine_part } => {
+                *machine_part = !*machine_part;
+            }
+            _ => {}
+        }
+    }
+
+    if input.just_pressed(KeyCode::KeyC)
+        && follow_up(&editor_level.definition.entities[index].kind).is_some()
+    {
+        editor_interaction.connecting_from = Some(ensure_id(&mut editor_level.definition, index));
+    }
+}
+
+/*
+Left click: finishes a connection, grabs the entity under the cursor or places a new one
+ */
+fn handle_editor_clicks(
+    // Globals
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    images: Res<Assets<Image>>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    editor_tool: Res<EditorTool>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut editor_interaction: ResMut<EditorInteraction>,
+    // Queries
+    level_entities: Query<(Entity, &LevelEntityIndex, &Sprite, &GlobalTransform)>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Some(cursor_position) = cursor_position_in_world(&windows, &camera_q) else {
+        return;
+    };
+
+    // note: the level is only borrowed mutably where it changes, any change respawns it (and ends a drag)
+    let hovered = hovered_entity(&images, &level_entities, cursor_position);
+
+    if let Some(connecting_from) = editor_interaction.connecting_from.take() {
+        let Some((_, index)) = hovered else {
+            return;
+        };
+
+        let definition = &mut editor_level.definition;
+        let target = ensure_id(definition, index);
+
+        let Some(source) = definition
+            .entities
+            .iter_mut()
+            .find(|entity| entity.id.as_ref() == Some(&connecting_from))
+        else {
+            return;
+        };
+
+        let Some(follow_up) = follow_up_mut(&mut source.kind) else {
+            return;
+        };
+
+        // clicking a follow up again disconnects it
+        if let Some(existing) = follow_up.iter().position(|id| *id == target) {
+            follow_up.remove(existing);
+        } else if target != connecting_from {
+            follow_up.push(target);
+        }
+
+        return;
+    }
+
+    match (hovered, *editor_tool) {
+        // rune slots are placed onto the entity that was clicked (unless it's a rune slot, that one is dragged)
+        (Some((entity, index)), EditorTool::RuneSlot)
+            if !matches!(
+                editor_level.definition.entities[index].kind,
+                LevelEntityKind::RuneSlot { .. }
+            ) =>
+        {
+            let Ok((_, _, _, global_transform)) = level_entities.get(entity) else {
+                return;
+            };
+
+            let definition = &mut editor_level.definition;
+            let attached_to = ensure_id(definition, index);
+            let position = global_transform
+                .affine()
+                .inverse()
+                .transform_point3(cursor_position.extend(0.0))
+                .truncate();
+
+            let mut rune_slot = EditorTool::RuneSlot.template(position);
+            rune_slot.kind = LevelEntityKind::RuneSlot {
+                attached_to,
+                teleport_to: None,
+            };
+            definition.entities.push(rune_slot);
+        }
+        (Some((entity, index)), _) => {
+            let Ok((_, _, _, global_transform)) = level_entities.get(entity) else {
+                return;
+            };
+
+            editor_interaction.dragged = Some(DraggedEntity {
+                entity,
+                index,
+                grab_offset: global_transform.translation().truncate() - cursor_position,
+            });
+        }
+        (None, EditorTool::RuneSlot) => {
+            warn!("Rune slots have to be placed onto a card or letter");
+        }
+        (None, EditorTool::DonutCircle) => {
+            let definition = &mut editor_level.definition;
+
+            // the machine has exactly one donut circle, so it is moved instead
+            match definition
+                .entities
+                .iter_mut()
+                .find(|entity| matches!(entity.kind, LevelEntityKind::DonutCircle))
+            {
+                Some(donut_circle) => donut_circle.position = cursor_position,
+                None => definition
+                    .entities
+                    .push(EditorTool::DonutCircle.template(cursor_position)),
+            }
+        }
+        (None, editor_tool) => {
+            editor_level
+                .definition
+                .entities
+                .push(editor_tool.template(cursor_position));
+        }
+    }
+}
+
+/*
+Moves the grabbed entity with the cursor; the level is only changed once it is dropped
+ */
+fn drag_edited_entity(
+    // Globals
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut editor_interaction: ResMut<EditorInteraction>,
+    // Queries
+    mut transforms: Query<(&mut Transform, Option<&ChildOf>)>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    let Some(dragged) = editor_interaction.dragged else {
+        return;
+    };
+
+    let Ok((mut transform, child_of)) = transforms.get_mut(dragged.entity) else {
+        editor_interaction.dragged = None;
+        return;
+    };
+
+    if let Some(cursor_position) = cursor_position_in_world(&windows, &camera_q) {
+        let position = cursor_position + dragged.grab_offset;
+
+        // rune slots are positioned relative to the entity they are attached to
+        let position =
+            match child_of.and_then(|child_of| global_transforms.get(child_of.parent()).ok()) {
+                Some(parent_transform) => parent_transform
+                    .affine()
+                    .inverse()
+                    .transform_point3(position.extend(0.0))
+                    .truncate(),
+                None => position,
+            };
+
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+
+    if mouse_input.just_released(MouseButton::Left) {
+        editor_level.definition.entities[dragged.index].position = transform.translation.truncate();
+        editor_interaction.dragged = None;
+    }
+}
+
+/*
+Shows the level as it is defined now
+ */
+fn respawn_edited_level(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    editor_level: Res<EditorLevel>,
+    // Queries
+    level_entities: Query<Entity, (With<LevelEntity>, Without<ChildOf>)>,
+) {
+    for level_entity in level_entities {
+        commands.entity(level_entity).despawn();
+    }
+
+    spawn_level(&mut commands, &asset_server, &editor_level.definition);
+}
+
+/*
+Runes all look alike, so the editor names their effect
+ */
+fn label_runes(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    runes: Query<(Entity, &Rune), Added<Rune>>,
+) {
+    for (entity, rune) in runes {
+        commands.entity(entity).with_child((
+            RuneLabel,
+            Text2d::new(format!("{:?}", rune.rune_effect.rune_effect_type)),
+            TextFont::from_font_size(RUNE_LABEL_FONT_SIZE),
+            Transform::from_translation(RUNE_LABEL_OFFSET),
+        ));
+    }
+}
+
+/*
+An arrow from every trigger star (gate, delay, metronome) to each of its follow ups, plus the connection being drawn
+ */
+fn draw_trigger_connections(
+    // Globals
+    mut gizmos: Gizmos,
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    editor_level: Res<EditorLevel>,
+    editor_interaction: Res<EditorInteraction>,
+    // Queries
+    level_entities: Query<(&LevelEntityId, &GlobalTransform)>,
+) {
+    let positions: HashMap<&str, Vec2> = level_entities
+        .iter()
+        .map(|(id, global_transform)| (id.0.as_str(), global_transform.translation().truncate()))
+        .collect();
+
+    for entity in &editor_level.definition.entities {
+        let (Some(id), Some(follow_up)) = (&entity.id, follow_up(&entity.kind)) else {
+            continue;
+        };
+
+        let Some(&start) = positions.get(id.as_str()) else {
+            continue;
+        };
+
+        for follow_up in follow_up {
+            if let Some(&end) = positions.get(follow_up.as_str()) {
+                gizmos.arrow_2d(start, end, CONNECTION_GOLD);
+            }
+        }
+    }
+
+    if let Some(connecting_from) = &editor_interaction.connecting_from
+        && let Some(&start) = positions.get(connecting_from.as_str())
+        && let Some(cursor_position) = cursor_position_in_world(&windows, &camera_q)
+    {
+        gizmos.line_2d(start, cursor_position, MAGICAL_BLUE);
+    }
+}
+
+fn update_editor_status(
+    // Globals
+    editor_tool: Res<EditorTool>,
+    editor_level: Res<EditorLevel>,
+    editor_file: Res<EditorFile>,
+    // Queries
+    editor_status: Query<&mut Text, With<EditorStatus>>,
+) {
+    let (test_balls, final_ball) = count_balls(&editor_level.definition);
+
+    for mut text in editor_status {
+        let status = format!(
+            "{}   placing: {}   test balls: {}   final ball: {}\n{}",
+            editor_file.path,
+            editor_tool.name(),
+            test_balls,
+            if final_ball { "yes" } else { "no" },
+            EDITOR_HELP
+        );
+
+        if text.0 != status {
+            text.0 = status;
+        }
+    }
+}
+
+/*
+========================================================================================
+Test runs
+========================================================================================
+*/
+
+fn schedule_return_to_editor(
+    // Globals
+    mut editor_test_run: ResMut<EditorTestRun>,
+) {
+    editor_test_run.return_timer = Some(Timer::from_seconds(
+        TEST_RUN_RETURN_SECONDS,
+        TimerMode::Once,
+    ));
+}
+
+fn advance_return_to_editor(
+    // Globals
+    time: Res<Time>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut editor_test_run: ResMut<EditorTestRun>,
+) {
+    if let Some(return_timer) = &mut editor_test_run.return_timer
+        && return_timer.tick(time.delta()).finished()
+    {
+        next_game_state.set(GameState::Editing);
+    }
+}
+
+/*
+F5 ends the test run at any time
+ */
+fn handle_test_run_key(
+    // Globals
+    input: Res<ButtonInput<KeyCode>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if input.just_pressed(KeyCode::F5) {
+        next_game_state.set(GameState::Editing);
+    }
+}
+
+/*
+========================================================================================
+Level files
+========================================================================================
+*/
+
+fn save_level(definition: &LevelDefinition, path: &str) {
+    let level_ron = match ron::ser::to_string_pretty(definition, ron::ser::PrettyConfig::default())
+    {
+        Ok(level_ron) => level_ron,
+        Err(error) => {
+            warn!("Could not serialize level: {}", error);
+            return;
+        }
+    };
+
+    match write_level_file(path, &level_ron) {
+        Ok(()) => info!("Level saved to {}", path),
+        Err(error) => warn!("Could not save level to {}: {}", path, error),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_level_file(path: &str) -> Result<Option<String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(level_ron) => Ok(Some(level_ron)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_level_file(path: &str, level_ron: &str) -> Result<(), String> {
+    std::fs::write(path, level_ron).map_err(|error| error.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_level_file(path: &str) -> Result<Option<String>, String> {
+    crate::save::read_data_file(path).map_err(|error| error.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_level_file(path: &str, level_ron: &str) -> Result<(), String> {
+    crate::save::write_data_file(path, level_ron).map_err(|error| error.to_string())
+}
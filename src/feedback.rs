@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+
+use crate::{
+    MachineFailedEvent, handle_event_machine_reset, level::LevelEntityId,
+    snapshot::restore_machine_snapshot,
+};
+
+/*
+========================================================================================
+Failure feedback
+
+When the machine fails, the reason is logged and the entities involved
+(the trigger star that was hit, the empty rune slot, ...) flash red for a moment
+-> the player sees where the machine went wrong, without any text
+========================================================================================
+*/
+
+// how long the entities involved in a failure stay highlighted
+const FAILURE_HIGHLIGHT_SECONDS: f32 = 2.0;
+
+const FAILURE_RED: Color = Color::LinearRgba(LinearRgba::rgb(1.0, 0.1, 0.1));
+
+pub struct FailureFeedbackPlugin;
+
+impl Plugin for FailureFeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        // note: after the machine has been reset (in the same physics step), so the highlight starts from
+        // and fades back to the colors the reset has restored, instead of overwriting them
+        app.add_systems(
+            FixedUpdate,
+            (
+                handle_event_machine_failed_feedback,
+                fade_failure_highlights,
+            )
+                .chain()
+                .after(handle_event_machine_reset)
+                .after(restore_machine_snapshot),
+        );
+    }
+}
+
+/*
+Marks an entity that was involved in the machine failing
+ */
+#[derive(Component)]
+struct FailureHighlight {
+    timer: Timer,
+    // the sprite fades back to it (e.g. the red of the donut circle)
+    original_color: Color,
+}
+
+/*
+Logs why the machine has failed and highlights the entities involved
+ */
+fn handle_event_machine_failed_feedback(
+    // Globals
+    mut commands: Commands,
+    mut machine_failed_event_reader: EventReader<MachineFailedEvent>,
+    // Queries
+    sprites: Query<(&Sprite, Option<&FailureHighlight>)>,
+    level_entity_ids: Query<&LevelEntityId>,
+) {
+    for machine_failed_event in machine_failed_event_reader.read() {
+        // ids as in the level file, so level designers know where to look
+        let involved_ids: Vec<&str> = machine_failed_event
+            .reason
+            .involved_entities()
+            .into_iter()
+            .filter_map(|entity| level_entity_ids.get(entity).ok())
+            .map(|level_entity_id| level_entity_id.0.as_str())
+            .collect();
+
+        info!(
+            "Machine has failed: {:?} (involved: {:?})",
+            machine_failed_event.reason, involved_ids
+        );
+
+        // entities may be gone already (e.g. the blue ball) or may not be visible (e.g. a gate without sprite)
+        for entity in machine_failed_event.reason.involved_entities() {
+            let Ok((sprite, failure_highlight)) = sprites.get(entity) else {
+                continue;
+            };
+
+            // an entity that is still highlighted from the last failure is red-ish already
+            let original_color = failure_highlight.map_or(sprite.color, |failure_highlight| {
+                failure_highlight.original_color
+            });
+
+            commands.entity(entity).insert(FailureHighlight {
+                timer: Timer::from_seconds(FAILURE_HIGHLIGHT_SECONDS, TimerMode::Once),
+                original_color,
+            });
+        }
+    }
+}
+
+/*
+Fades highlighted entities from red back to their normal color
+ */
+fn fade_failure_highlights(
+    // Globals
+    mut commands: Commands,
+    time: Res<Time<Fixed>>,
+    // Queries
+    highlighted: Query<(Entity, &mut FailureHighlight, &mut Sprite)>,
+) {
+    for (entity, mut failure_highlight, mut sprite) in highlighted {
+        failure_highlight.timer.tick(time.delta());
+
+        sprite.color = FAILURE_RED.mix(
+            &failure_highlight.original_color,
+            failure_highlight.timer.fraction(),
+        );
+
+        if failure_highlight.timer.finished() {
+            commands.entity(entity).remove::<FailureHighlight>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,47 @@
+use super::*;
+use crate::{
+    GameState, MachineFailureReason,
+    simulation::{BallKind, MachineOutcome, Simulation},
+};
+
+const FIRST_STEPS: &str = "levels/01_first_steps.level.ron";
+
+#[test]
+fn failure_highlight_fades_back_to_the_color_after_the_reset() {
+    let mut simulation = Simulation::with_plugins(FIRST_STEPS, FailureFeedbackPlugin);
+
+    assert!(simulation.place_rune("move_right_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Test));
+
+    // the dead end star has been activated (blue) when the machine fails there
+    assert!(matches!(
+        simulation.run_machine(),
+        MachineOutcome::Failed(MachineFailureReason::DeadEndTriggerStar { .. })
+    ));
+    assert_eq!(simulation.game_state(), GameState::Building);
+
+    let card_stop_star = simulation.entity("card_stop_star");
+    assert!(
+        simulation
+            .world()
+            .get::<FailureHighlight>(card_stop_star)
+            .is_some()
+    );
+
+    simulation.step((FAILURE_HIGHLIGHT_SECONDS * 60.0) as u32 + 1);
+
+    assert!(
+        simulation
+            .world()
+            .get::<FailureHighlight>(card_stop_star)
+            .is_none()
+    );
+    assert_eq!(
+        simulation
+            .world()
+            .get::<Sprite>(card_stop_star)
+            .unwrap()
+            .color,
+        Color::WHITE
+    );
+}
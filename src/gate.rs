@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState, MAGICAL_BLUE, MachineFailedEvent, MachineFailureReason, StarTrigger,
+    TriggerStarActivatedEvent,
+    timing::{DelayNode, MetronomeNode},
+};
+
+/*
+========================================================================================
+Logic gates
+
+Gates combine several branches of the chain: trigger stars (or other gates) list a gate as their follow up,
+and the gate only triggers its own follow ups once its condition is met
+-> several parallel chains can meet later on
+Gates are reset whenever the machine is started
+========================================================================================
+*/
+
+pub struct GatePlugin;
+
+impl Plugin for GatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnTransition {
+                exited: GameState::Building,
+                entered: GameState::MachineRunning,
+            },
+            reset_gates,
+        )
+        // note: on the fixed timestep, like the rest of the machine
+        .add_systems(
+            FixedUpdate,
+            (handle_event_gate_activated, check_gate_windows, fire_gates)
+                .chain()
+                .run_if(in_state(GameState::MachineRunning)),
+        );
+    }
+}
+
+/*
+Condition of a gate; inputs are all trigger stars and gates that have the gate as follow up
+ */
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum GateType {
+    // fires once every input has been activated
+    And,
+    // fires as soon as any input is activated
+    Or,
+    // fires if exactly one input has been activated within the window (in seconds) after the first activation
+    // more than one input activated -> the machine fails
+    Xor { window_seconds: f32 },
+    // fires once the inputs have been activated this many times in total
+    Counter { hits: u32 },
+    // fires if no input has been activated within the window (in seconds) after the machine was started
+    Not { window_seconds: f32 },
+}
+
+#[derive(Component)]
+pub struct Gate {
+    gate_type: GateType,
+    follow_up_entities: Vec<Entity>,
+    // distinct inputs that have been activated during this run
+    activated_inputs: Vec<Entity>,
+    // total number of activations during this run
+    hits: u32,
+    // time since the window of Xor/Not gates has started
+    window_timer: Option<Timer>,
+    // condition is met, the follow ups are triggered by fire_gates
+    ready: bool,
+    // a gate fires at most once per run
+    done: bool,
+}
+
+impl Gate {
+    pub fn new(gate_type: GateType, follow_up_entities: Vec<Entity>) -> Self {
+        Gate {
+            gate_type,
+            follow_up_entities,
+            activated_inputs: Vec::new(),
+            hits: 0,
+            window_timer: None,
+            ready: false,
+            done: false,
+        }
+    }
+
+    /*
+    Whether the gate is still waiting for its window to end (the machine is not stuck)
+     */
+    pub fn is_waiting(&self) -> bool {
+        !self.done && self.window_timer.is_some()
+    }
+}
+
+/*
+Clears whatever the gates have seen during the previous run; Not gates start their window
+ */
+fn reset_gates(
+    // Queries
+    gates: Query<(&mut Gate, Option<&mut Sprite>)>,
+) {
+    for (mut gate, sprite) in gates {
+        gate.activated_inputs.clear();
+        gate.hits = 0;
+        gate.ready = false;
+        gate.done = false;
+        gate.window_timer = match gate.gate_type {
+            GateType::Not { window_seconds } => {
+                Some(Timer::from_seconds(window_seconds, TimerMode::Once))
+            }
+            _ => None,
+        };
+
+        if let Some(mut sprite) = sprite {
+            sprite.color = Color::WHITE;
+        }
+    }
+}
+
+/*
+Collects activations of the gates and marks them ready once their condition is met
+ */
+fn handle_event_gate_activated(
+    // Globals
+    mut trigger_event_reader: EventReader<TriggerStarActivatedEvent>,
+    mut machine_failed_event_writer: EventWriter<MachineFailedEvent>,
+    // Queries
+    mut gates: Query<(Entity, &mut Gate, Option<&mut Sprite>)>,
+    star_triggers: Query<&StarTrigger>,
+    delay_nodes: Query<&DelayNode>,
+    metronome_nodes: Query<&MetronomeNode>,
+) {
+    for trigger_event in trigger_event_reader.read() {
+        let gate_entity = trigger_event.entity_to_be_triggered;
+        let triggered_by = trigger_event.triggered_by;
+
+        if !gates.contains(gate_entity) {
+            continue;
+        }
+
+        // number of inputs has to be known before borrowing the gate mutably
+        // note: every node of the chain can be an input (trigger stars, gates, delays, metronomes)
+        let input_count = star_triggers
+            .iter()
+            .map(|star_trigger| star_trigger.follow_up_entities.as_slice())
+            .chain(
+                gates
+                    .iter()
+                    .map(|(_, gate, _)| gate.follow_up_entities.as_slice()),
+            )
+            .chain(delay_nodes.iter().map(DelayNode::follow_up_entities))
+            .chain(
+                metronome_nodes
+                    .iter()
+                    .map(MetronomeNode::follow_up_entities),
+            )
+            .filter(|follow_up_entities| follow_up_entities.contains(&gate_entity))
+            .count();
+
+        let (_, mut gate, sprite) = gates.get_mut(gate_entity).ok().unwrap();
+
+        if gate.ready || gate.done {
+            continue;
+        }
+
+        gate.hits += 1;
+        if !gate.activated_inputs.contains(&triggered_by) {
+            gate.activated_inputs.push(triggered_by);
+        }
+
+        // show the player that the gate has received something
+        if let Some(mut sprite) = sprite {
+            sprite.color = MAGICAL_BLUE;
+        }
+
+        trace!(
+            "gate {} activated by {} ({} of {} inputs, {} hits)",
+            gate_entity,
+            triggered_by,
+            gate.activated_inputs.len(),
+            input_count,
+            gate.hits
+        );
+
+        match gate.gate_type {
+            GateType::And => {
+                if gate.activated_inputs.len() >= input_count {
+                    gate.ready = true;
+                }
+            }
+            GateType::Or => {
+                gate.ready = true;
+            }
+            GateType::Xor { window_seconds } => {
+                if gate.activated_inputs.len() > 1 {
+                    info!("Gate {} received more than one input", gate_entity);
+                    gate.done = true;
+                    machine_failed_event_writer.write(MachineFailedEvent {
+                        reason: MachineFailureReason::GateConflict { gate: gate_entity },
+                    });
+                } else if gate.window_timer.is_none() {
+                    gate.window_timer = Some(Timer::from_seconds(window_seconds, TimerMode::Once));
+                }
+            }
+            GateType::Counter { hits } => {
+                if gate.hits >= hits {
+                    gate.ready = true;
+                }
+            }
+            GateType::Not { .. } => {
+                // the gate will never fire during this run
+                gate.done = true;
+            }
+        }
+    }
+}
+
+/*
+Xor and Not gates are ready once their window is over
+ */
+fn check_gate_windows(
+    // Globals
+    time: Res<Time<Fixed>>,
+    // Queries
+    gates: Query<&mut Gate>,
+) {
+    for mut gate in gates {
+        if gate.ready || gate.done {
+            continue;
+        }
+
+        let Some(window_timer) = &mut gate.window_timer else {
+            continue;
+        };
+
+        if window_timer.tick(time.delta()).just_finished() {
+            gate.ready = true;
+        }
+    }
+}
+
+/*
+Activates all follow up entities of gates whose condition is met
+ */
+fn fire_gates(
+    // Globals
+    mut trigger_event_writer: EventWriter<TriggerStarActivatedEvent>,
+    // Queries
+    gates: Query<(Entity, &mut Gate)>,
+) {
+    for (gate_entity, mut gate) in gates {
+        if !gate.ready || gate.done {
+            continue;
+        }
+
+        gate.done = true;
+
+        trigger_event_writer.write_batch(gate.follow_up_entities.iter().map(|follow_up_entity| {
+            TriggerStarActivatedEvent {
+                entity_to_be_triggered: *follow_up_entity,
+                triggered_by: gate_entity,
+            }
+        }));
+
+        debug!("gate {} fired", gate_entity);
+    }
+}
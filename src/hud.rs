@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+
+use crate::{BlueBall, FINAL_BALL_GOLD, FinalBall, GameState, GreyBall, MAGICAL_BLUE};
+
+/*
+========================================================================================
+HUD
+
+Shows the state of the current level without any text:
+- a status light that lights up blue while the machine is running
+- one ball per remaining attempt; test balls are white, the final ball is gold
+- a star next to the balls once only the final ball is left
+========================================================================================
+*/
+
+const HUD_STATUS_IMAGE: &str = "UI Pack/PNG/Blue/Double/check_round_round_circle.png";
+const HUD_BALL_IMAGE: &str = "Puzzle Assets/PNG/Double/ballGrey.png";
+const HUD_FINAL_ATTEMPT_IMAGE: &str = "UI Pack/PNG/Grey/Double/star_outline.png";
+
+const HUD_ICON_SIZE: f32 = 40.0;
+
+const HUD_IDLE_GREY: Color = Color::LinearRgba(LinearRgba::rgb(0.5, 0.5, 0.5));
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        // note: Building is entered again after every run and pause, the HUD is only spawned once per level
+        // the level editor has no HUD, only its test runs have one
+        app.add_systems(
+            OnEnter(GameState::Building),
+            show_hud.run_if(not(any_with_component::<Hud>)),
+        )
+        .add_systems(OnEnter(GameState::MainMenu), hide_hud)
+        .add_systems(OnEnter(GameState::Editing), hide_hud)
+        .add_systems(Update, (update_hud_status, update_hud_attempts));
+    }
+}
+
+/*
+Marks the root UI node of the HUD
+ */
+#[derive(Component)]
+struct Hud;
+
+#[derive(Component)]
+struct HudStatus;
+
+/*
+Parent of the ball icons, one per remaining attempt
+ */
+#[derive(Component, Default)]
+struct HudAttempts {
+    // (test balls, final balls) the icons currently show
+    shown: (usize, usize),
+}
+
+#[derive(Component)]
+struct HudFinalAttempt;
+
+/*
+Spawns the HUD in the top left corner once a level is started
+ */
+fn show_hud(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands
+        .spawn((
+            Hud,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(16.0),
+                top: Val::Px(16.0),
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(8.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                HudStatus,
+                ImageNode::new(asset_server.load(HUD_STATUS_IMAGE)).with_color(HUD_IDLE_GREY),
+                hud_icon_node(),
+            ));
+
+            parent.spawn((
+                HudAttempts::default(),
+                Node {
+                    column_gap: Val::Px(4.0),
+                    ..default()
+                },
+            ));
+
+            parent.spawn((
+                HudFinalAttempt,
+                ImageNode::new(asset_server.load(HUD_FINAL_ATTEMPT_IMAGE)),
+                hud_icon_node(),
+                Visibility::Hidden,
+            ));
+        });
+}
+
+/*
+Removes the HUD when the player returns to the level select screen
+ */
+fn hide_hud(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    huds: Query<Entity, With<Hud>>,
+) {
+    for hud in huds {
+        commands.entity(hud).despawn();
+    }
+}
+
+/*
+Lights up the status icon while the machine is running
+ */
+fn update_hud_status(
+    // Globals
+    game_state: Res<State<GameState>>,
+    // Queries
+    hud_status: Query<&mut ImageNode, With<HudStatus>>,
+) {
+    let status_color = match game_state.get() {
+        GameState::MachineRunning => MAGICAL_BLUE,
+        _ => HUD_IDLE_GREY,
+    };
+
+    for mut image_node in hud_status {
+        image_node.color = status_color;
+    }
+}
+
+/*
+Shows one ball per remaining attempt and marks the final attempt
+the star only shows up once all test balls are used up
+ */
+fn update_hud_attempts(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    // Queries
+    // note: a ball keeps its GreyBall component while it is flying as blue ball
+    grey_balls: Query<Has<FinalBall>, (With<GreyBall>, Without<BlueBall>)>,
+    hud_attempts: Query<(Entity, &mut HudAttempts)>,
+    hud_final_attempt: Query<&mut Visibility, With<HudFinalAttempt>>,
+) {
+    let final_balls_left = grey_balls.iter().filter(|is_final| *is_final).count();
+    let test_balls_left = grey_balls.iter().count() - final_balls_left;
+
+    for (hud_attempts_entity, mut hud_attempts) in hud_attempts {
+        if hud_attempts.shown == (test_balls_left, final_balls_left) {
+            continue;
+        }
+
+        hud_attempts.shown = (test_balls_left, final_balls_left);
+
+        let mut hud_attempts_commands = commands.entity(hud_attempts_entity);
+        hud_attempts_commands.despawn_related::<Children>();
+
+        hud_attempts_commands.with_children(|parent| {
+            // test balls first, the final ball comes last
+            let ball_colors = std::iter::repeat_n(Color::WHITE, test_balls_left)
+                .chain(std::iter::repeat_n(FINAL_BALL_GOLD, final_balls_left));
+
+            for ball_color in ball_colors {
+                parent.spawn((
+                    ImageNode::new(asset_server.load(HUD_BALL_IMAGE)).with_color(ball_color),
+                    hud_icon_node(),
+                ));
+            }
+        });
+    }
+
+    // levels without a final ball: the last test ball is the final attempt
+    let final_attempt = if final_balls_left > 0 {
+        test_balls_left == 0
+    } else {
+        test_balls_left == 1
+    };
+
+    let final_attempt_visibility = if final_attempt {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    for mut visibility in hud_final_attempt {
+        visibility.set_if_neq(final_attempt_visibility);
+    }
+}
+
+fn hud_icon_node() -> Node {
+    Node {
+        width: Val::Px(HUD_ICON_SIZE),
+        height: Val::Px(HUD_ICON_SIZE),
+        ..default()
+    }
+}
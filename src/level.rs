@@ -0,0 +1,621 @@
+use avian2d::prelude::*;
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    AddCollider, AdjustableBallFiringThingy, BACKGROUND_RENDER_LAYER,
+    BALL_FIRING_THINGY_RENDER_LAYER, BALL_RENDER_LAYER, BallFiringThingy, BallFiringThingyArrow,
+    Card, ColliderType, DONUT_CIRCLE_RED, DONUT_CIRCLE_RENDER_LAYER, DonutCircle, FINAL_BALL_GOLD,
+    FinalBall, GreyBall, Letter, MAGICAL_BLUE, MachinePart, Pickable, Portal,
+    RUNE_EXPLANATION_LAYER, RUNE_MACHINE_RENDER_LAYER, RUNE_RENDER_LAYER, RUNE_SLOT_RENDER_LAYER,
+    RenderLayer, Rune, RuneEffect, RuneSlot, StarTrigger,
+    gate::{Gate, GateType},
+    score::{LevelScoring, LevelStats},
+    stall::StallDetection,
+    timing::{DelayNode, MetronomeNode},
+    trajectory::TrajectoryPreview,
+};
+
+/*
+========================================================================================
+Level files
+
+A level is a RON file in assets/levels that lists every entity of a machine
+Entities can be given a symbolic id, which other entities use to reference them (rune slots, trigger graph)
+-> designers can build new machines without touching any Rust code
+========================================================================================
+*/
+
+// rune slots are scaled up a bit so the runes fit in nicely
+const RUNE_SLOT_SCALE: f32 = 1.3;
+// distance between ball firing thingy and its arrow
+const BALL_FIRING_THINGY_ARROW_DISTANCE: f32 = 64.0;
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<LevelDefinition>()
+            .init_asset_loader::<LevelLoader>()
+            .add_systems(Update, spawn_loaded_level);
+    }
+}
+
+/*
+Holds the handle of the level that is currently played
+spawned is set once all entities of the level have been spawned, so it only happens once
+ */
+#[derive(Resource)]
+pub struct CurrentLevel {
+    // None for levels outside of the campaign (e.g. test runs from the level editor)
+    pub campaign_index: Option<usize>,
+    pub handle: Handle<LevelDefinition>,
+    pub spawned: bool,
+}
+
+/*
+Marks every entity that belongs to the current level, so the level can be removed again
+ */
+#[derive(Component)]
+pub struct LevelEntity;
+
+/*
+Symbolic id of a level entity, as given in the level file
+-> tools (e.g. the headless simulation) can find entities the same way the level file references them
+ */
+#[derive(Component)]
+pub struct LevelEntityId(pub String);
+
+/*
+Position of a level entity in the level file; unlike the id, every entity has one
+-> replays can reference entities that have no id
+ */
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LevelEntityIndex(pub usize);
+
+/*
+========================================================================================
+Level format
+========================================================================================
+*/
+
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone)]
+pub struct LevelDefinition {
+    // shows where the ball will go while building; turn off for harder puzzles
+    #[serde(default = "default_trajectory_preview")]
+    pub trajectory_preview: bool,
+    // when a running machine counts as failed (idle, timeout, out of the play area)
+    #[serde(default)]
+    pub stall_detection: StallDetection,
+    // score thresholds for two and three stars
+    #[serde(default)]
+    pub scoring: LevelScoring,
+    pub entities: Vec<LevelEntityDefinition>,
+}
+
+fn default_trajectory_preview() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LevelEntityDefinition {
+    // symbolic id; only needed if another entity references this one
+    #[serde(default)]
+    pub id: Option<String>,
+    pub kind: LevelEntityKind,
+    // note: for rune slots, this is relative to the entity they are attached to
+    pub position: Vec2,
+    // in degrees, counter-clockwise
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default)]
+    pub sprite: Option<LevelSpriteDefinition>,
+    #[serde(default)]
+    pub collider: Option<LevelColliderDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LevelSpriteDefinition {
+    pub image: String,
+    #[serde(default)]
+    pub custom_size: Option<Vec2>,
+}
+
+/*
+Turned into an AddCollider component, so the collider is created once the sprite has been loaded
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LevelColliderDefinition {
+    pub collider_scale: f32,
+    pub collider_type: ColliderType,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum LevelEntityKind {
+    Rune {
+        effect: RuneEffect,
+    },
+    Decoration {
+        layer: DecorationLayer,
+    },
+    // test ball; failing with it costs nothing but the ball
+    GreyBall,
+    // the ball that decides the level: it has to succeed, there is no second chance
+    FinalBall,
+    // fires to the left, unless rotated
+    BallFiringThingy {
+        firing_speed: f32,
+        arrow_image: String,
+        // lets the player aim the thingy and pick a launch power
+        #[serde(default)]
+        adjustable: Option<LevelAimDefinition>,
+    },
+    DonutCircle,
+    Card {
+        machine_part: bool,
+    },
+    Letter {
+        machine_part: bool,
+    },
+    RuneSlot {
+        attached_to: String,
+        // where a teleport rune placed in this slot sends the entity the slot is attached to
+        #[serde(default)]
+        teleport_to: Option<String>,
+    },
+    StarTrigger {
+        // all of these are triggered when the star is activated; none means the machine fails
+        follow_up: Vec<String>,
+    },
+    Connector {
+        size: Vec2,
+    },
+    // combines several trigger stars (those listing the gate as follow up); sprite is optional
+    Gate {
+        gate: GateType,
+        follow_up: Vec<String>,
+    },
+    // triggers its follow ups some time after it has been activated; sprite is optional
+    Delay {
+        seconds: f32,
+        follow_up: Vec<String>,
+    },
+    // triggers its follow ups every interval, once activated (or from the start of the machine with autostart)
+    Metronome {
+        interval_seconds: f32,
+        // ticks until the machine stops if not set
+        #[serde(default)]
+        repetitions: Option<u32>,
+        #[serde(default)]
+        autostart: bool,
+        follow_up: Vec<String>,
+    },
+    // the blue ball and moving parts entering the portal come out of the linked portal; needs a collider
+    Portal {
+        linked_to: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LevelAimDefinition {
+    // in degrees per mouse wheel step
+    pub rotation_step: f32,
+    // firing speeds the player can choose from; the thingy starts with the first one
+    pub power_levels: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum DecorationLayer {
+    Background,
+    RuneExplanation,
+}
+
+/*
+========================================================================================
+Loading
+========================================================================================
+*/
+
+#[derive(Default)]
+struct LevelLoader;
+
+#[derive(Debug, Error)]
+enum LevelLoaderError {
+    #[error("could not read level file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse level file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for LevelLoader {
+    type Asset = LevelDefinition;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        Ok(ron::de::from_bytes::<LevelDefinition>(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/*
+Waits for the current level to be loaded and spawns it (once)
+ */
+fn spawn_loaded_level(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<LevelDefinition>>,
+    current_level: Option<ResMut<CurrentLevel>>,
+) {
+    let Some(mut current_level) = current_level else {
+        return;
+    };
+
+    if current_level.spawned {
+        return;
+    }
+
+    if let Some(level) = levels.get(&current_level.handle) {
+        spawn_level(&mut commands, &asset_server, level);
+        current_level.spawned = true;
+
+        info!("Level spawned");
+    } else {
+        trace!("Level not yet loaded");
+    }
+}
+
+/*
+Spawns all entities of a level
+
+every entity is spawned (almost) empty first, so references by id can be resolved regardless of the order in the file
+ */
+pub fn spawn_level(commands: &mut Commands, asset_server: &AssetServer, level: &LevelDefinition) {
+    commands.insert_resource(TrajectoryPreview {
+        enabled: level.trajectory_preview,
+    });
+    commands.insert_resource(level.stall_detection.clone());
+    commands.insert_resource(level.scoring.clone());
+    commands.insert_resource(LevelStats::default());
+
+    let entities: Vec<Entity> = level
+        .entities
+        .iter()
+        .map(|_| commands.spawn(LevelEntity).id())
+        .collect();
+
+    let mut entities_by_id: HashMap<&str, Entity> = HashMap::default();
+
+    for (definition, entity) in level.entities.iter().zip(entities.iter()) {
+        if let Some(id) = &definition.id
+            && entities_by_id.insert(id.as_str(), *entity).is_some()
+        {
+            warn!("Level contains id '{}' more than once", id);
+        }
+    }
+
+    let resolve = |id: &str| -> Option<Entity> {
+        let entity = entities_by_id.get(id).copied();
+
+        if entity.is_none() {
+            warn!("Level references unknown id '{}'", id);
+        }
+
+        entity
+    };
+
+    for (index, (definition, entity)) in level.entities.iter().zip(entities).enumerate() {
+        let position = definition.position;
+        let rotation = Quat::from_rotation_z(definition.rotation.to_radians());
+        let sprite = definition.sprite(asset_server);
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(LevelEntityIndex(index));
+
+        if let Some(id) = &definition.id {
+            entity_commands.insert(LevelEntityId(id.clone()));
+        }
+
+        if let Some(collider) = &definition.collider {
+            entity_commands.insert(AddCollider {
+                collider_scale: collider.collider_scale,
+                collider_type: collider.collider_type,
+            });
+        }
+
+        match &definition.kind {
+            LevelEntityKind::Rune { effect } => {
+                entity_commands.insert((
+                    Rune {
+                        default_position: position,
+                        affected_entity: None,
+                        rune_effect: *effect,
+                    },
+                    Pickable,
+                    RenderLayer {
+                        render_layer: RUNE_RENDER_LAYER,
+                    },
+                    Transform::from_translation(position.extend(RUNE_RENDER_LAYER)),
+                    sprite,
+                ));
+            }
+            LevelEntityKind::Decoration { layer } => {
+                let render_layer = match layer {
+                    DecorationLayer::Background => BACKGROUND_RENDER_LAYER,
+                    DecorationLayer::RuneExplanation => RUNE_EXPLANATION_LAYER,
+                };
+
+                entity_commands.insert((
+                    RenderLayer { render_layer },
+                    Transform::from_translation(position.extend(render_layer)),
+                    sprite,
+                ));
+            }
+            LevelEntityKind::GreyBall => {
+                entity_commands.insert((
+                    GreyBall {
+                        default_position: position,
+                    },
+                    sprite,
+                    Transform::from_translation(position.extend(BALL_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: BALL_RENDER_LAYER,
+                    },
+                    Pickable,
+                    RigidBody::Kinematic,
+                ));
+            }
+            LevelEntityKind::FinalBall => {
+                let mut final_ball_sprite = sprite;
+                final_ball_sprite.color = FINAL_BALL_GOLD;
+
+                entity_commands.insert((
+                    GreyBall {
+                        default_position: position,
+                    },
+                    FinalBall,
+                    final_ball_sprite,
+                    Transform::from_translation(position.extend(BALL_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: BALL_RENDER_LAYER,
+                    },
+                    Pickable,
+                    RigidBody::Kinematic,
+                ));
+            }
+            LevelEntityKind::BallFiringThingy {
+                firing_speed,
+                arrow_image,
+                adjustable,
+            } => {
+                // arrow is placed next to the thingy, pointing in firing direction; it rotates with the thingy
+                let arrow_position = Vec2::NEG_X * BALL_FIRING_THINGY_ARROW_DISTANCE;
+
+                let firing_speed = adjustable
+                    .as_ref()
+                    .and_then(|adjustable| adjustable.power_levels.first().copied())
+                    .unwrap_or(*firing_speed);
+
+                if let Some(adjustable) = adjustable {
+                    entity_commands.insert(AdjustableBallFiringThingy {
+                        rotation_step: adjustable.rotation_step.to_radians(),
+                        power_levels: adjustable.power_levels.clone(),
+                        power_level: 0,
+                    });
+                }
+
+                entity_commands
+                    .insert((
+                        BallFiringThingy { firing_speed },
+                        sprite,
+                        Transform::from_translation(
+                            position.extend(BALL_FIRING_THINGY_RENDER_LAYER),
+                        ),
+                        RenderLayer {
+                            render_layer: BALL_FIRING_THINGY_RENDER_LAYER,
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            LevelEntity,
+                            BallFiringThingyArrow,
+                            Sprite::from_image(asset_server.load(arrow_image)),
+                            Transform::from_translation(arrow_position.extend(0.0)),
+                        ));
+                    });
+            }
+            LevelEntityKind::DonutCircle => {
+                let mut donut_circle_sprite = sprite;
+                donut_circle_sprite.color = DONUT_CIRCLE_RED;
+
+                entity_commands.insert((
+                    DonutCircle,
+                    Transform::from_translation(position.extend(DONUT_CIRCLE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: DONUT_CIRCLE_RENDER_LAYER,
+                    },
+                    donut_circle_sprite,
+                ));
+            }
+            LevelEntityKind::Card { machine_part } => {
+                entity_commands.insert((
+                    Card,
+                    Transform::from_translation(position.extend(RUNE_MACHINE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: RUNE_MACHINE_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+
+                if *machine_part {
+                    entity_commands.insert(MachinePart);
+                }
+            }
+            LevelEntityKind::Letter { machine_part } => {
+                entity_commands.insert((
+                    Letter,
+                    Transform::from_translation(position.extend(RUNE_MACHINE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: RUNE_MACHINE_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+
+                if *machine_part {
+                    entity_commands.insert(MachinePart);
+                }
+            }
+            LevelEntityKind::RuneSlot {
+                attached_to,
+                teleport_to,
+            } => {
+                // rune slots are child entities, so their z is relative to the machine part
+                let rune_slot_render_layer = RUNE_SLOT_RENDER_LAYER - RUNE_MACHINE_RENDER_LAYER;
+
+                entity_commands.insert((
+                    RuneSlot {
+                        teleport_target: teleport_to.as_deref().and_then(resolve),
+                    },
+                    Transform::from_translation(position.extend(rune_slot_render_layer))
+                        .with_scale(Vec3::splat(RUNE_SLOT_SCALE)),
+                    RenderLayer {
+                        render_layer: RUNE_SLOT_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+
+                if let Some(parent) = resolve(attached_to) {
+                    entity_commands.insert(ChildOf(parent));
+                }
+            }
+            LevelEntityKind::StarTrigger { follow_up } => {
+                entity_commands.insert((
+                    StarTrigger {
+                        follow_up_entities: follow_up
+                            .iter()
+                            .filter_map(|follow_up| resolve(follow_up))
+                            .collect(),
+                    },
+                    Transform::from_translation(position.extend(RUNE_MACHINE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: RUNE_MACHINE_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+            }
+            LevelEntityKind::Gate { gate, follow_up } => {
+                entity_commands.insert((
+                    Gate::new(
+                        *gate,
+                        follow_up
+                            .iter()
+                            .filter_map(|follow_up| resolve(follow_up))
+                            .collect(),
+                    ),
+                    Transform::from_translation(position.extend(RUNE_MACHINE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: RUNE_MACHINE_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+            }
+            LevelEntityKind::Delay { seconds, follow_up } => {
+                entity_commands.insert((
+                    DelayNode::new(
+                        *seconds,
+                        follow_up
+                            .iter()
+                            .filter_map(|follow_up| resolve(follow_up))
+                            .collect(),
+                    ),
+                    Transform::from_translation(position.extend(RUNE_MACHINE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: RUNE_MACHINE_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+            }
+            LevelEntityKind::Metronome {
+                interval_seconds,
+                repetitions,
+                autostart,
+                follow_up,
+            } => {
+                entity_commands.insert((
+                    MetronomeNode::new(
+                        *interval_seconds,
+                        *repetitions,
+                        *autostart,
+                        follow_up
+                            .iter()
+                            .filter_map(|follow_up| resolve(follow_up))
+                            .collect(),
+                    ),
+                    Transform::from_translation(position.extend(RUNE_MACHINE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: RUNE_MACHINE_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+            }
+            LevelEntityKind::Portal { linked_to } => {
+                entity_commands.insert((
+                    Portal {
+                        linked_portal: resolve(linked_to),
+                    },
+                    // things pass through portals, they don't bounce off
+                    Sensor,
+                    Transform::from_translation(position.extend(RUNE_MACHINE_RENDER_LAYER)),
+                    RenderLayer {
+                        render_layer: RUNE_MACHINE_RENDER_LAYER,
+                    },
+                    sprite,
+                ));
+            }
+            LevelEntityKind::Connector { size } => {
+                entity_commands.insert((
+                    Transform::from_translation(position.extend(BACKGROUND_RENDER_LAYER)),
+                    Sprite::from_color(MAGICAL_BLUE, *size),
+                ));
+            }
+        }
+
+        // every kind inserts its own transform, the rotation is the same for all of them
+        entity_commands
+            .entry::<Transform>()
+            .and_modify(move |mut transform| transform.rotation = rotation);
+    }
+}
+
+impl LevelEntityDefinition {
+    /*
+    Creates the sprite of this entity; entities without an image get an empty default sprite
+     */
+    fn sprite(&self, asset_server: &AssetServer) -> Sprite {
+        let Some(sprite_definition) = &self.sprite else {
+            return Sprite::default();
+        };
+
+        let mut sprite = Sprite::from_image(asset_server.load(&sprite_definition.image));
+        sprite.custom_size = sprite_definition.custom_size;
+
+        sprite
+    }
+}
@@ -1,5 +1,42 @@
 use avian2d::prelude::*;
-use bevy::{log::*, prelude::*};
+use bevy::{input::mouse::MouseWheel, log::*, prelude::*};
+use serde::{Deserialize, Serialize};
+
+mod campaign;
+mod editor;
+mod feedback;
+mod gate;
+mod hud;
+mod level;
+mod replay;
+mod save;
+mod score;
+#[cfg(test)]
+mod simulation;
+mod snapshot;
+#[cfg(test)]
+mod solver;
+mod stall;
+mod time_control;
+mod timing;
+mod trajectory;
+mod undo;
+
+use campaign::CampaignPlugin;
+use editor::EditorPlugin;
+use feedback::FailureFeedbackPlugin;
+use gate::{Gate, GatePlugin};
+use hud::HudPlugin;
+use level::{LevelEntity, LevelPlugin};
+use replay::{ReplayPlayback, ReplayPlugin};
+use save::SavePlugin;
+use score::{LevelScore, LevelScoring, LevelStats, ScoringPlugin};
+use snapshot::SnapshotPlugin;
+use stall::StallDetectionPlugin;
+use time_control::TimeControlPlugin;
+use timing::{DelayNode, MetronomeNode, TimingPlugin};
+use trajectory::TrajectoryPreviewPlugin;
+use undo::UndoPlugin;
 
 fn main() {
     App::new()
@@ -9,52 +46,170 @@ fn main() {
             level: Level::TRACE,
             ..Default::default()
         }))
-        // Add Default Physics
-        // length unit 100 => 1m = 1 pixels.
-        .add_plugins(PhysicsPlugins::default().with_length_unit(1.0))
+        // Everything the machine needs to run; also used by the headless simulation
+        .add_plugins(MachinePlugin)
+        // Level progression & level select
+        .add_plugins(CampaignPlugin)
+        // Dotted preview of the ball path while building
+        .add_plugins(TrajectoryPreviewPlugin)
+        // Shows the player why the machine has failed
+        .add_plugins(FailureFeedbackPlugin)
+        // Attempts left & machine status
+        .add_plugins(HudPlugin)
+        // Undo & redo of rune placements while building
+        .add_plugins(UndoPlugin)
+        // note: after the campaign, as it replaces the campaign progress with the saved one
+        .add_plugins(SavePlugin)
+        // Recording & playback of everything the player does in a level
+        .add_plugins(ReplayPlugin)
+        // Speed, freezing & single steps of a running machine
+        .add_plugins(TimeControlPlugin)
+        // Building levels in the game, with the mouse
+        .add_plugins(EditorPlugin)
         // Debug physics
         //.add_plugins(PhysicsDebugPlugin::default())
+        // Game flow
+        .add_systems(OnEnter(GameState::Paused), pause_game)
+        .add_systems(OnExit(GameState::Paused), resume_game)
         // Startup
-        .add_systems(Startup, setup_mvp_scene)
+        .add_systems(Startup, setup_game)
         // Input handling
         .add_systems(
             PreUpdate,
             (
                 handle_event_mouse_move,
-                handle_event_pick,
-                handle_event_release,
-                handle_event_trigger_star_activated,
-                handle_event_machine_failed,
-            ),
+                handle_event_pick.run_if(on_event::<PickEvent>),
+                handle_event_release.run_if(on_event::<ReleaseEvent>),
+            )
+                .run_if(in_state(GameState::Building))
+                // a replay plays the level instead of the player
+                .run_if(not(resource_exists::<ReplayPlayback>)),
         )
-        // game logic
-        .add_systems(Update, apply_rune_effects)
-        // Collision handling
         .add_systems(
-            PostUpdate,
+            Update,
             (
-                handle_collision_ball_with_ball_firing_thingy,
-                handle_collision_rune_with_rune_slot,
-                handle_collision_blue_ball_and_runes,
-                handle_collision_rune_effect_with_trigger_star,
-                handle_collision_blue_ball_with_trigger_star,
+                handle_ball_firing_thingy_aiming
+                    .run_if(in_state(GameState::Building))
+                    .run_if(not(resource_exists::<ReplayPlayback>)),
+                update_ball_firing_thingy_arrows,
+                toggle_pause,
             ),
         )
         // Input forwarding
-        .add_systems(FixedUpdate, controls)
-        // Add colliders to sprites
-        .add_systems(Last, add_colliders)
+        .add_systems(
+            FixedUpdate,
+            controls
+                .run_if(in_state(GameState::Building))
+                .run_if(not(resource_exists::<ReplayPlayback>)),
+        )
         //Events
         .add_event::<PickEvent>()
         .add_event::<ReleaseEvent>()
-        .add_event::<TriggerStarActivatedEvent>()
-        .add_event::<MachineFailedEvent>()
         // Ressources
         .insert_resource(ClearColor(Color::srgb(0.4, 0.4, 0.6)))
         // Run
         .run();
 }
 
+/*
+The machine itself: levels, physics, rune placement, ball launch, trigger chain, win & fail
+no input handling and nothing that needs a window -> runs headless as well (see simulation.rs)
+ */
+pub struct MachinePlugin;
+
+impl Plugin for MachinePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Add Default Physics
+            // length unit 100 => 1m = 1 pixels.
+            .add_plugins(PhysicsPlugins::default().with_length_unit(1.0))
+            // Level loading
+            .add_plugins(LevelPlugin)
+            // Reset of machine parts after a failed run
+            .add_plugins(SnapshotPlugin)
+            // Logic gates combining several branches of the chain
+            .add_plugins(GatePlugin)
+            // Delays and metronomes in the chain
+            .add_plugins(TimingPlugin)
+            // Fails machines that are stuck, run too long or leave the screen
+            .add_plugins(StallDetectionPlugin)
+            // Score & stars of won levels
+            .add_plugins(ScoringPlugin)
+            // gravity is in pixels per second squared, the default would be barely noticeable with a length unit of 1
+            .insert_resource(Gravity(Vec2::NEG_Y * GRAVITY))
+            // Game flow
+            .init_state::<GameState>()
+            // the machine runs on the fixed timestep, just like physics -> the same actions always lead to the same run,
+            // no matter how many frames are drawn meanwhile (see replay.rs)
+            // state changes requested by the machine (e.g. won, failed) take effect with the next physics step as well
+            .add_systems(FixedPreUpdate, apply_game_state_transition)
+            // Event handling
+            .add_systems(
+                FixedUpdate,
+                (
+                    // note: chained, a donut summoned in the same physics step as a failure wins (see handle_event_machine_failed)
+                    (
+                        handle_event_trigger_star_activated,
+                        handle_event_machine_failed,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::MachineRunning)),
+                    handle_event_machine_reset
+                        .after(handle_event_trigger_star_activated)
+                        .after(handle_event_machine_failed)
+                        .run_if(on_event::<MachineResetEvent>),
+                ),
+            )
+            // game logic
+            .add_systems(
+                FixedUpdate,
+                (
+                    apply_rune_effects,
+                    apply_gravity_rune_effects,
+                    apply_teleport_rune_effects,
+                    apply_impulse_rune_effects,
+                    // forces are applied by the physics step that follows
+                    apply_sideways_gravity,
+                )
+                    .after(handle_event_machine_reset)
+                    .run_if(in_state(GameState::MachineRunning)),
+            )
+            // Collision handling, once the physics step has found the contacts
+            .add_systems(
+                FixedPostUpdate,
+                (
+                    (
+                        handle_collision_ball_with_ball_firing_thingy,
+                        handle_collision_rune_with_rune_slot,
+                    )
+                        .run_if(in_state(GameState::Building)),
+                    (
+                        handle_collision_blue_ball_and_runes,
+                        handle_collision_rune_effect_with_trigger_star,
+                        handle_collision_blue_ball_with_trigger_star,
+                        handle_collision_portals,
+                    )
+                        .run_if(in_state(GameState::MachineRunning)),
+                )
+                    .after(PhysicsSet::Sync),
+            )
+            // Test run results are shown until the next ball is launched
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Building,
+                    entered: GameState::MachineRunning,
+                },
+                despawn_summoned_donuts,
+            )
+            // Add colliders to sprites
+            .add_systems(Last, add_colliders)
+            //Events
+            .add_event::<TriggerStarActivatedEvent>()
+            .add_event::<MachineFailedEvent>()
+            .add_event::<MachineResetEvent>();
+    }
+}
+
 /*
 ========================================================================================
 Constants
@@ -82,11 +237,24 @@ const BALL_FIRING_THINGY_RENDER_LAYER: f32 = 99.0;
 const RUNE_RENDER_LAYER: f32 = 100.0;
 const BALL_RENDER_LAYER: f32 = 101.0;
 
+// ARROWS
+
+// scale of the ball firing thingy arrow at lowest/highest power level
+const ARROW_MIN_SCALE: f32 = 0.6;
+const ARROW_MAX_SCALE: f32 = 1.4;
+
+// PHYSICS
+
+// in pixels per second squared; only affects dynamic bodies (e.g. machine parts hit by a gravity rune)
+const GRAVITY: f32 = 600.0;
+
 // COLORS
 
 const MAGICAL_BLUE: Color = Color::LinearRgba(LinearRgba::rgb(0.3, 0.3, 1.0));
 const DONUT_CIRCLE_RED: Color = Color::LinearRgba(LinearRgba::rgb(1.0, 0.2, 0.2));
 const BURN_OUT_BLACK: Color = Color::LinearRgba(LinearRgba::rgb(0.3, 0.1, 0.1));
+const PAUSE_OVERLAY_BLACK: Color = Color::LinearRgba(LinearRgba::new(0.0, 0.0, 0.0, 0.5));
+const FINAL_BALL_GOLD: Color = Color::LinearRgba(LinearRgba::rgb(1.0, 0.8, 0.2));
 
 /*
 ========================================================================================
@@ -95,539 +263,16 @@ Systems
  */
 
 /*
-spawns all runes, objects etc. for the MVP scene
-explanation: MVP - Minimum Viable product
-means a scene that shows all basic gameplay elements which is loaded by default during development
-
-after finishing the MVP scene I can consider doing more than one scene
+spawns the meta entities
+the game starts in the level select screen (GameState::MainMenu); levels are spawned by the LevelPlugin once their file has been loaded
 */
-fn setup_mvp_scene(
+fn setup_game(
     // Globals
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
 ) {
-    /*
-    =========================================================================================================
-    spawn meta entities
-    =========================================================================================================
-     */
-    // spawn player entity
-    commands.spawn((Player, PlayerCanPick));
-
     // spawn camera
     commands.spawn(Camera2d);
 
-    /*
-    =========================================================================================================
-    spawn runes & their explanations
-    =========================================================================================================
-     */
-    let mut render_layer = RUNE_RENDER_LAYER;
-
-    let mut rune_default_position;
-
-    rune_default_position = Vec2::new(-530.0, -290.0);
-
-    //spawn upward movement rune
-    commands.spawn((
-        Rune {
-            default_position: rune_default_position,
-            affected_entity: None,
-            rune_effect: RuneEffect {
-                rune_effect_type: RuneEffectType::MoveUp,
-                rune_effect_move_speed: Some(Vec2::new(0.0, 200.0)),
-            },
-        },
-        Pickable,
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Transform::from_xyz(
-            rune_default_position.x,
-            rune_default_position.y,
-            render_layer,
-        ),
-        Sprite::from_image(asset_server.load("runes/PNG/Grey/Slab/runeGrey_slab_001.png")),
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Rectangle,
-        },
-    ));
-
-    render_layer = RUNE_EXPLANATION_LAYER;
-
-    // spawn upward movement rune explanation
-    commands.spawn((
-        Transform::from_xyz(
-            rune_default_position.x,
-            rune_default_position.y,
-            render_layer,
-        ),
-        Sprite::from_image(asset_server.load("UI Pack/PNG/Blue/Double/arrow_decorative_n.png")),
-        RenderLayer { render_layer },
-    ));
-
-    render_layer = RUNE_RENDER_LAYER;
-    rune_default_position = Vec2::new(-380.0, -290.0);
-
-    //spawn right movement rune
-    commands.spawn((
-        Rune {
-            default_position: rune_default_position,
-            affected_entity: None,
-            rune_effect: RuneEffect {
-                rune_effect_type: RuneEffectType::MoveRight,
-                rune_effect_move_speed: Some(Vec2::new(200.0, 0.0)),
-            },
-        },
-        Pickable,
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Transform::from_xyz(
-            rune_default_position.x,
-            rune_default_position.y,
-            render_layer,
-        ),
-        Sprite::from_image(asset_server.load("runes/PNG/Grey/Slab/runeGrey_slab_002.png")),
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Rectangle,
-        },
-    ));
-
-    // spawn right movement rune explanation
-    render_layer = RUNE_EXPLANATION_LAYER;
-
-    commands.spawn((
-        Transform::from_xyz(
-            rune_default_position.x,
-            rune_default_position.y,
-            render_layer,
-        ),
-        Sprite::from_image(asset_server.load("UI Pack/PNG/Blue/Double/arrow_decorative_e.png")),
-        RenderLayer { render_layer },
-    ));
-
-    /*
-    =========================================================================================================
-    Spawn Pickable object area
-    =========================================================================================================
-     */
-    render_layer = BACKGROUND_RENDER_LAYER;
-
-    commands.spawn((
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Transform::from_xyz(-448.0, -296.0, render_layer),
-        Sprite::from_image(
-            asset_server.load("UI Pack/PNG/Blue/Double/button_rectangle_depth_line.png"),
-        ),
-    ));
-
-    commands.spawn((
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Transform::from_xyz(448.0, -296.0, render_layer),
-        Sprite::from_image(
-            asset_server.load("UI Pack/PNG/Blue/Double/button_rectangle_depth_line.png"),
-        ),
-    ));
-
-    /*
-    =========================================================================================================
-    Spawn grey balls
-    =========================================================================================================
-     */
-
-    let grey_ball_sprite =
-        Sprite::from_image(asset_server.load("Puzzle Assets/PNG/Double/ballGrey.png"));
-
-    let mut grey_ball_default_position = Vec2::new(350.0, -296.0);
-
-    render_layer = BALL_RENDER_LAYER;
-
-    commands.spawn((
-        GreyBall {
-            default_position: grey_ball_default_position.clone(),
-        },
-        grey_ball_sprite.clone(),
-        Transform::from_xyz(
-            grey_ball_default_position.x,
-            grey_ball_default_position.y,
-            render_layer,
-        ),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Circle,
-        },
-        Pickable,
-        RigidBody::Kinematic,
-    ));
-
-    grey_ball_default_position = Vec2::new(450.0, -296.0);
-
-    commands.spawn((
-        GreyBall {
-            default_position: grey_ball_default_position.clone(),
-        },
-        grey_ball_sprite.clone(),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Transform::from_xyz(
-            grey_ball_default_position.x,
-            grey_ball_default_position.y,
-            render_layer,
-        ),
-        Pickable,
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Circle,
-        },
-        RigidBody::Kinematic,
-    ));
-
-    grey_ball_default_position = Vec2::new(550.0, -296.0);
-
-    commands.spawn((
-        GreyBall {
-            default_position: grey_ball_default_position.clone(),
-        },
-        grey_ball_sprite.clone(),
-        Transform::from_xyz(
-            grey_ball_default_position.x,
-            grey_ball_default_position.y,
-            render_layer,
-        ),
-        Pickable,
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Circle,
-        },
-        RigidBody::Kinematic,
-        RenderLayer { render_layer },
-    ));
-
-    // Ball firing Thingy
-    let ball_firing_thingy_sprite = Sprite::from_image(
-        asset_server.load("UI Pack/PNG/Blue/Double/check_round_round_circle.png"),
-    );
-
-    render_layer = BALL_FIRING_THINGY_RENDER_LAYER;
-
-    let ball_firing_thingy_transform = Transform::from_xyz(580.0, -100.0, render_layer);
-
-    // note: default direction of BallFiringThingy is to the left
-    commands
-        .spawn((
-            BallFiringThingy {
-                // speed is units per second; see addition of physics plugin to determine how much that is in pixels
-                firing_direction: Vec2::new(-200.0, 0.0),
-            },
-            ball_firing_thingy_sprite,
-            ball_firing_thingy_transform,
-            RenderLayer {
-                render_layer: render_layer,
-            },
-            AddCollider {
-                collider_scale: 0.4,
-                collider_type: ColliderType::Circle,
-            },
-        ))
-        .with_children(|parent| {
-            // spawn arrow of ball firing thingy
-            parent.spawn((
-                Sprite::from_image(
-                    asset_server.load("UI Pack/PNG/Grey/Double/arrow_decorative_w.png"),
-                ),
-                Transform::from_xyz(-64.0, 0.0, 0.0),
-            ));
-        });
-
-    /*
-    =========================================================================================================
-    spawn target donut
-    =========================================================================================================
-     */
-
-    // spawn target donut presentation circle of mystic holyness
-    let mut donut_circle_sprite =
-        Sprite::from_image(asset_server.load("Particle Pack/PNG (Transparent)/magic_02.png"));
-    donut_circle_sprite.custom_size = Some(Vec2::new(200.0, 200.0));
-    donut_circle_sprite.color = DONUT_CIRCLE_RED;
-
-    render_layer = DONUT_CIRCLE_RENDER_LAYER;
-
-    let magic_circle_entity = commands
-        .spawn((
-            DonutCircle,
-            Transform::from_xyz(0.0, -280.0, render_layer),
-            RenderLayer {
-                render_layer: render_layer,
-            },
-            donut_circle_sprite,
-        ))
-        .id();
-
-    /*
-    =========================================================================================================
-    spawn cards
-    =========================================================================================================
-     */
-    //spawn 10 of hearts
-    render_layer = RUNE_MACHINE_RENDER_LAYER;
-
-    commands.spawn((
-        Card,
-        Transform::from_xyz(-450.0, 200.0, render_layer),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Sprite::from_image(asset_server.load("Boardgame Pack/PNG/Cards/cardHearts10.png")),
-    ));
-
-    //spawn jack of hearts
-    commands.spawn((
-        Card,
-        Transform::from_xyz(-250.0, 200.0, render_layer),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Sprite::from_image(asset_server.load("Boardgame Pack/PNG/Cards/cardHeartsJ.png")),
-    ));
-
-    //spawn queen of hearts
-    commands.spawn((
-        Card,
-        Transform::from_xyz(-50.0, 200.0, render_layer),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Sprite::from_image(asset_server.load("Boardgame Pack/PNG/Cards/cardHeartsQ.png")),
-    ));
-
-    //spawn king of hearts
-    commands.spawn((
-        Card,
-        Transform::from_xyz(150.0, 200.0, render_layer),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        Sprite::from_image(asset_server.load("Boardgame Pack/PNG/Cards/cardHeartsK.png")),
-    ));
-
-    let rune_slot_render_layer: f32 = RUNE_SLOT_RENDER_LAYER - render_layer;
-
-    //spawn ace of hearts
-    let card_rune_slot = commands
-        .spawn((
-            RuneSlot,
-            Transform::from_xyz(0.0, 0.0, rune_slot_render_layer).with_scale(Vec3::splat(1.3)),
-            RenderLayer {
-                render_layer: RUNE_SLOT_RENDER_LAYER,
-            },
-            Sprite::from_image(asset_server.load("runes/PNG/Black/Slab/runeBlack_slab_036.png")),
-            AddCollider {
-                collider_scale: 0.5,
-                collider_type: ColliderType::Rectangle,
-            },
-        ))
-        .id();
-
-    commands
-        .spawn((
-            Card,
-            MachinePart,
-            Transform::from_xyz(350.0, -100.0, render_layer),
-            RenderLayer {
-                render_layer: render_layer,
-            },
-            Sprite::from_image(asset_server.load("Boardgame Pack/PNG/Cards/cardHeartsA.png")),
-            AddCollider {
-                collider_scale: 1.0,
-                collider_type: ColliderType::Rectangle,
-            },
-        ))
-        .add_child(card_rune_slot);
-
-    /*
-    =========================================================================================================
-    Spawn Letters
-    =========================================================================================================
-     */
-
-    let letter_rune_slot_entity = commands
-        .spawn((
-            RuneSlot,
-            Transform::from_xyz(-50.0, 0.0, rune_slot_render_layer).with_scale(Vec3::splat(1.3)),
-            RenderLayer {
-                render_layer: RUNE_SLOT_RENDER_LAYER,
-            },
-            Sprite::from_image(asset_server.load("runes/PNG/Black/Slab/runeBlack_slab_036.png")),
-            AddCollider {
-                collider_scale: 0.5,
-                collider_type: ColliderType::Rectangle,
-            },
-        ))
-        .id();
-
-    let mut letter_sprite =
-        Sprite::from_image(asset_server.load("letters/Letter Tiles Redux/PNG/StyleH/tileD.png"));
-    letter_sprite.custom_size = Some(Vec2::new(100.0, 100.0));
-
-    commands
-        .spawn((
-            letter_sprite,
-            Letter,
-            MachinePart,
-            RenderLayer { render_layer },
-            Transform::from_xyz(-500.0, -50.0, render_layer),
-            AddCollider {
-                collider_scale: 1.0,
-                collider_type: ColliderType::Rectangle,
-            },
-        ))
-        .add_child(letter_rune_slot_entity);
-
-    // spawn O
-    letter_sprite =
-        Sprite::from_image(asset_server.load("letters/Letter Tiles Redux/PNG/StyleH/tileO.png"));
-    letter_sprite.custom_size = Some(Vec2::new(100.0, 100.0));
-
-    commands.spawn((
-        letter_sprite,
-        Letter,
-        RenderLayer { render_layer },
-        Transform::from_xyz(-200.0, -50.0, render_layer),
-    ));
-
-    // spawn N
-    letter_sprite =
-        Sprite::from_image(asset_server.load("letters/Letter Tiles Redux/PNG/StyleH/tileN.png"));
-    letter_sprite.custom_size = Some(Vec2::new(100.0, 100.0));
-
-    commands.spawn((
-        letter_sprite,
-        Letter,
-        RenderLayer { render_layer },
-        Transform::from_xyz(-90.0, -50.0, render_layer),
-    ));
-
-    // spawn U
-    letter_sprite =
-        Sprite::from_image(asset_server.load("letters/Letter Tiles Redux/PNG/StyleH/tileU.png"));
-    letter_sprite.custom_size = Some(Vec2::new(100.0, 100.0));
-
-    commands.spawn((
-        letter_sprite,
-        Letter,
-        RenderLayer { render_layer },
-        Transform::from_xyz(20.0, -50.0, render_layer),
-    ));
-
-    // spawn T
-    letter_sprite =
-        Sprite::from_image(asset_server.load("letters/Letter Tiles Redux/PNG/StyleH/tileT.png"));
-    letter_sprite.custom_size = Some(Vec2::new(100.0, 100.0));
-
-    commands.spawn((
-        letter_sprite,
-        Letter,
-        RenderLayer { render_layer },
-        Transform::from_xyz(130.0, -50.0, render_layer),
-    ));
-
-    /*
-    =========================================================================================================
-    spawn trigger stars
-    =========================================================================================================
-     */
-    let mut trigger_star_sprite =
-        Sprite::from_image(asset_server.load("UI Pack/PNG/Grey/Double/star_outline.png"));
-    trigger_star_sprite.custom_size = Some(Vec2::new(60.0, 60.0));
-
-    // spawn star for cards
-    commands.spawn((
-        StarTrigger {
-            follow_up_entity: Some(letter_rune_slot_entity),
-        },
-        Transform::from_xyz(350.0, 320.0, render_layer),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Rectangle,
-        },
-        trigger_star_sprite.clone(),
-    ));
-
-    // spawn star for letters
-    commands.spawn((
-        StarTrigger {
-            follow_up_entity: Some(magic_circle_entity),
-        },
-        Transform::from_xyz(-230.0, -120.0, render_layer),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Rectangle,
-        },
-        trigger_star_sprite.clone(),
-    ));
-
-    // spawn star to stop card
-    commands.spawn((
-        StarTrigger {
-            follow_up_entity: None,
-        },
-        Transform::from_xyz(530.0, 0.0, render_layer),
-        RenderLayer {
-            render_layer: render_layer,
-        },
-        AddCollider {
-            collider_scale: 1.0,
-            collider_type: ColliderType::Rectangle,
-        },
-        trigger_star_sprite.clone(),
-    ));
-
-    /*
-    =========================================================================================================
-    spawn connectors
-    =========================================================================================================
-     */
-    let mut connector_sprite = Sprite::from_color(MAGICAL_BLUE, Vec2::new(900.0, 10.0));
-    commands.spawn((
-        Transform::from_xyz(-110.0, 320.0, BACKGROUND_RENDER_LAYER),
-        connector_sprite.clone(),
-    ));
-    connector_sprite.custom_size = Some(Vec2::new(10.0, 380.0));
-    commands.spawn((
-        Transform::from_xyz(-555.0, 130.0, BACKGROUND_RENDER_LAYER),
-        connector_sprite.clone(),
-    ));
-
-    connector_sprite.custom_size = Some(Vec2::new(230.0, 10.0));
-    commands.spawn((
-        Transform::from_xyz(-115.0, -120.0, BACKGROUND_RENDER_LAYER),
-        connector_sprite.clone(),
-    ));
-
-    connector_sprite.custom_size = Some(Vec2::new(10.0, 100.0));
-    commands.spawn((
-        Transform::from_xyz(0.0, -165.0, BACKGROUND_RENDER_LAYER),
-        connector_sprite.clone(),
-    ));
-
     info!("Game Start");
 }
 
@@ -658,7 +303,7 @@ fn add_colliders(
                 ColliderType::Circle => {
                     // add circle collider
                     let collider_size = add_collider.collider_scale
-                        * (calculate_sprite_size(&images, &sprite, &transform.scale).x * 0.5);
+                        * (calculate_sprite_size(&images, sprite, &transform.scale).x * 0.5);
 
                     commands
                         .entity(entity)
@@ -668,7 +313,7 @@ fn add_colliders(
                 }
                 ColliderType::Rectangle => {
                     let collider_size = add_collider.collider_scale
-                        * calculate_sprite_size(&images, &sprite, &transform.scale);
+                        * calculate_sprite_size(&images, sprite, &transform.scale);
 
                     commands
                         .entity(entity)
@@ -695,19 +340,18 @@ fn handle_collision_ball_with_ball_firing_thingy(
             &mut Transform,
             &mut LinearVelocity,
             &mut Sprite,
+            Has<FinalBall>,
         ),
-        (With<Placed>, Without<Player>),
+        With<Placed>,
     >,
-    player: Single<(Entity, &Player)>,
     // Globals
     mut commands: Commands,
+    mut next_game_state: ResMut<NextState<GameState>>,
     //Collisions
     collisions: Collisions,
     // Queries
-    ball_firing_thingies: Query<
-        (&BallFiringThingy, &Transform),
-        (Without<Placed>, Without<Player>),
-    >,
+    ball_firing_thingies: Query<(&BallFiringThingy, &Transform), Without<Placed>>,
+    final_balls: Query<(), With<FinalBall>>,
 ) {
     trace!("Handling potential collision between blue ball and ball firing thingy");
 
@@ -717,6 +361,7 @@ fn handle_collision_ball_with_ball_firing_thingy(
         mut placed_ball_transform,
         mut placed_ball_velocity,
         mut placed_ball_sprite,
+        placed_ball_is_final,
     ) = placed_ball.into_inner();
 
     // remove placed immediately, regardless of actual collision
@@ -745,20 +390,23 @@ fn handle_collision_ball_with_ball_firing_thingy(
             // I'll just tint the sprite instead of replacing it...
             placed_ball_sprite.color = MAGICAL_BLUE;
 
-            //mark player as wiating for machine
-            commands.entity(player.0).insert(PlayerWaitingForMachine);
+            // player is now waiting for the machine
+            next_game_state.set(GameState::MachineRunning);
 
-            //place ball in firing thingy
-            let entity_ball_firing_thingy: Entity;
+            // levels without a final ball are decided by whichever ball succeeds first
+            commands.insert_resource(MachineRun {
+                final_run: placed_ball_is_final || final_balls.is_empty(),
+            });
 
+            //place ball in firing thingy
             // place ball at the transform of firing thingy
-            if contact_pair.collider1.eq(&placed_ball_entity) {
-                entity_ball_firing_thingy = contact_pair.collider2;
+            let entity_ball_firing_thingy = if contact_pair.collider1.eq(&placed_ball_entity) {
+                contact_pair.collider2
             } else
             // colliders2 is placed ball
             {
-                entity_ball_firing_thingy = contact_pair.collider1;
-            }
+                contact_pair.collider1
+            };
 
             let ball_firing_thingy_transform = ball_firing_thingies
                 .get(entity_ball_firing_thingy)
@@ -770,14 +418,14 @@ fn handle_collision_ball_with_ball_firing_thingy(
             placed_ball_transform.translation.x = ball_firing_thingy_transform.translation.x;
             placed_ball_transform.translation.y = ball_firing_thingy_transform.translation.y;
 
-            // fire ball in direction of firing thingy
-            // TODO consider rotation of ball firing_thingy (from Transform-component)
-            placed_ball_velocity.0 = ball_firing_thingies
+            // fire ball in direction of firing thingy (considering its rotation)
+            let (ball_firing_thingy, ball_firing_thingy_transform) = ball_firing_thingies
                 .get(entity_ball_firing_thingy)
                 .ok()
-                .unwrap()
-                .0
-                .firing_direction;
+                .unwrap();
+
+            placed_ball_velocity.0 =
+                ball_firing_thingy.firing_velocity(ball_firing_thingy_transform);
 
             // placed ball can no longer be picked -> remove Pickable component
             commands.entity(placed_ball_entity).remove::<Pickable>();
@@ -832,13 +480,11 @@ fn handle_collision_rune_with_rune_slot(
         {
             trace!("Rune placed in rune slot, handling...");
 
-            let entity_rune_slot: Entity;
-
-            if contact_pair.collider1.eq(&rune_entity) {
-                entity_rune_slot = contact_pair.collider2;
+            let entity_rune_slot = if contact_pair.collider1.eq(&rune_entity) {
+                contact_pair.collider2
             } else {
-                entity_rune_slot = contact_pair.collider1;
-            }
+                contact_pair.collider1
+            };
 
             // move rune into rune-slot by setting rune-translation to zero and making it a child
             commands.entity(entity_rune_slot).add_child(rune_entity);
@@ -930,21 +576,19 @@ fn handle_collision_blue_ball_and_runes(
 
             commands.entity(blue_ball.0).despawn();
 
-            let rune_entity: Entity;
-
             // move rune effect component from rune to affected entity
-            if contact_pair.collider1.eq(&blue_ball.0) {
-                rune_entity = contact_pair.collider2;
+            let rune_entity = if contact_pair.collider1.eq(&blue_ball.0) {
+                contact_pair.collider2
             } else {
-                rune_entity = contact_pair.collider1;
-            }
+                contact_pair.collider1
+            };
 
             // move the RuneEffect from the rune entity to the affected entity (as provided by the RuneSlot)
             let (rune, mut sprite) = runes.get_inner(rune_entity).ok().unwrap();
 
             commands
                 .entity(rune.affected_entity.unwrap())
-                .insert(rune.rune_effect.clone());
+                .insert(rune.rune_effect);
 
             // change color tint
             sprite.color = MAGICAL_BLUE;
@@ -960,8 +604,6 @@ fn handle_collision_blue_ball_and_runes(
 Handles collisions between TriggerStars and Entities affected by RuneEffects
  */
 fn handle_collision_rune_effect_with_trigger_star(
-    // Execution Condition
-    _player: Single<&Player, With<PlayerWaitingForMachine>>,
     // Globals
     mut commands: Commands,
     mut trigger_event_writer: EventWriter<TriggerStarActivatedEvent>,
@@ -969,105 +611,66 @@ fn handle_collision_rune_effect_with_trigger_star(
     //Collisions
     collisions: Collisions,
     // Queries
-    trigger_stars: Query<
+    mut trigger_stars: Query<
         (Entity, &StarTrigger, &mut Sprite),
-        (
-            Without<Player>,
-            Without<RuneEffect>,
-            Without<RuneSlot>,
-            Without<Rune>,
-        ),
+        (Without<RuneEffect>, Without<RuneSlot>, Without<Rune>),
     >,
     rune_affected_entities: Query<
         (Entity, &RuneEffect),
-        (
-            Without<Player>,
-            Without<StarTrigger>,
-            Without<RuneSlot>,
-            Without<Rune>,
-        ),
+        (Without<StarTrigger>, Without<RuneSlot>, Without<Rune>),
     >,
     rune_slots: Query<
         (&RuneSlot, &ChildOf, &Children),
-        (
-            Without<Player>,
-            Without<StarTrigger>,
-            Without<RuneEffect>,
-            Without<Rune>,
-        ),
+        (Without<StarTrigger>, Without<RuneEffect>, Without<Rune>),
     >,
     mut runes: Query<
         (&Rune, &mut Sprite),
-        (
-            Without<Player>,
-            Without<StarTrigger>,
-            Without<RuneEffect>,
-            Without<RuneSlot>,
-        ),
+        (Without<StarTrigger>, Without<RuneEffect>, Without<RuneSlot>),
     >,
 ) {
-    trace!("Player cannot pick -> assume machine is running -> handle star tigger collisions");
+    trace!("Machine is running -> handle star tigger collisions");
 
-    let mut trigger_star_entity: Option<Entity> = None;
-    let mut affected_entity: Option<Entity> = None;
+    // parallel branches of the chain may hit their stars in the same frame
+    let mut activations: Vec<(Entity, Entity)> = Vec::new();
 
     for contact_pair in collisions.iter() {
-        if (trigger_stars.contains(contact_pair.collider1)
-            || trigger_stars.contains(contact_pair.collider2))
-            && (rune_affected_entities.contains(contact_pair.collider1)
-                || rune_affected_entities.contains(contact_pair.collider2))
+        if trigger_stars.contains(contact_pair.collider1)
+            && rune_affected_entities.contains(contact_pair.collider2)
         {
-            // handle collision between TriggerStar and affected entity
-
-            if trigger_stars.contains(contact_pair.collider1) {
-                trigger_star_entity =
-                    Some(trigger_stars.get(contact_pair.collider1).ok().unwrap().0);
-
-                affected_entity = Some(
-                    rune_affected_entities
-                        .get(contact_pair.collider2)
-                        .ok()
-                        .unwrap()
-                        .0,
-                );
-            } else {
-                trigger_star_entity =
-                    Some(trigger_stars.get(contact_pair.collider2).ok().unwrap().0);
-
-                affected_entity = Some(
-                    rune_affected_entities
-                        .get(contact_pair.collider1)
-                        .ok()
-                        .unwrap()
-                        .0,
-                );
-            }
-
-            // trigger star and affected entity have been identified -> interrupt the loop
-            break;
+            activations.push((contact_pair.collider1, contact_pair.collider2));
+        } else if trigger_stars.contains(contact_pair.collider2)
+            && rune_affected_entities.contains(contact_pair.collider1)
+        {
+            activations.push((contact_pair.collider2, contact_pair.collider1));
         }
     }
 
-    // overwrite trigger star to get mutable access to the sprite (consumes the query)
-    if let Some(trigger_star_entity) = trigger_star_entity {
-        let mut trigger_star = trigger_stars.get_inner(trigger_star_entity).ok().unwrap();
+    for (trigger_star_entity, affected_entity) in activations {
+        let (_, trigger_star, mut trigger_star_sprite) =
+            trigger_stars.get_mut(trigger_star_entity).ok().unwrap();
 
         // paint trigger star blue
-        trigger_star.2.color = MAGICAL_BLUE;
+        trigger_star_sprite.color = MAGICAL_BLUE;
 
-        // trigger follow up mechanic
-        if let Some(follow_up_entity) = trigger_star.1.follow_up_entity {
-            trigger_event_writer.write(TriggerStarActivatedEvent {
-                entity_to_be_triggered: follow_up_entity,
+        // trigger follow up mechanic; every follow up entity starts its own branch of the chain
+        if trigger_star.follow_up_entities.is_empty() {
+            // no follow up entity set -> reset the machine
+            machine_failed_event_writer.write(MachineFailedEvent {
+                reason: MachineFailureReason::DeadEndTriggerStar {
+                    trigger_star: trigger_star_entity,
+                    machine_part: affected_entity,
+                },
             });
         } else {
-            // no follow up entity set -> reset the machine
-            machine_failed_event_writer.write(MachineFailedEvent);
+            trigger_event_writer.write_batch(trigger_star.follow_up_entities.iter().map(
+                |follow_up_entity| TriggerStarActivatedEvent {
+                    entity_to_be_triggered: *follow_up_entity,
+                    triggered_by: trigger_star_entity,
+                },
+            ));
         }
-    }
 
-    // handle deactivation of affected entity
-    if let Some(affected_entity) = affected_entity {
+        // handle deactivation of affected entity
         // remove rune effect from affected entity
         commands.entity(affected_entity).remove::<RuneEffect>();
 
@@ -1092,7 +695,6 @@ fn handle_collision_rune_effect_with_trigger_star(
 /* Handles the event when a blue ball hits a trigger star directly - meaning no rune was ever hit and the machine has failed (without running) */
 fn handle_collision_blue_ball_with_trigger_star(
     // Execution Condition
-    _player: Single<&Player, With<PlayerWaitingForMachine>>,
     blue_ball: Single<Entity, With<BlueBall>>,
     // Globals
     mut commands: Commands,
@@ -1108,13 +710,115 @@ fn handle_collision_blue_ball_with_trigger_star(
             && (star_triggers.contains(contact_pair.collider1)
                 || star_triggers.contains(contact_pair.collider2))
         {
+            let trigger_star = if star_triggers.contains(contact_pair.collider1) {
+                contact_pair.collider1
+            } else {
+                contact_pair.collider2
+            };
+
             commands.entity(blue_ball.entity()).despawn();
-            machine_failed_writer.write(MachineFailedEvent);
+            machine_failed_writer.write(MachineFailedEvent {
+                reason: MachineFailureReason::BallMissedRunes { trigger_star },
+            });
             break;
         }
     }
 }
 
+/*
+Handles collisions between portals and the blue ball or entities affected by RuneEffects
+whatever enters a portal comes out of the linked portal; its velocity is turned along with the portal orientation
+ */
+fn handle_collision_portals(
+    // Globals
+    mut commands: Commands,
+    //Collisions
+    collisions: Collisions,
+    // Queries
+    portals: Query<(&Portal, &Transform)>,
+    mut travellers: Query<
+        (
+            Entity,
+            &mut Transform,
+            Option<&mut LinearVelocity>,
+            Option<&mut RuneEffect>,
+            Option<&Teleported>,
+        ),
+        (Or<(With<BlueBall>, With<RuneEffect>)>, Without<Portal>),
+    >,
+) {
+    // travellers that are touching any portal; they keep their Teleported marker until they have left all portals
+    let mut travellers_in_portals: Vec<Entity> = Vec::new();
+
+    for contact_pair in collisions.iter() {
+        let (portal_entity, traveller_entity) = if portals.contains(contact_pair.collider1)
+            && travellers.contains(contact_pair.collider2)
+        {
+            (contact_pair.collider1, contact_pair.collider2)
+        } else if portals.contains(contact_pair.collider2)
+            && travellers.contains(contact_pair.collider1)
+        {
+            (contact_pair.collider2, contact_pair.collider1)
+        } else {
+            continue;
+        };
+
+        // a traveller touching several portals is only handled once
+        if travellers_in_portals.contains(&traveller_entity) {
+            continue;
+        }
+        travellers_in_portals.push(traveller_entity);
+
+        let (_, mut transform, linear_velocity, rune_effect, teleported) =
+            travellers.get_mut(traveller_entity).ok().unwrap();
+
+        // the traveller has just come out of a portal -> don't send it right back
+        if teleported.is_some() {
+            continue;
+        }
+
+        let (portal, portal_transform) = portals.get(portal_entity).ok().unwrap();
+
+        let Some((_, exit_portal_transform)) = portal
+            .linked_portal
+            .and_then(|linked_portal| portals.get(linked_portal).ok())
+        else {
+            warn!("Portal {} is not linked to another portal", portal_entity);
+            continue;
+        };
+
+        trace!(
+            "{} teleported by portal {}",
+            traveller_entity, portal_entity
+        );
+
+        transform.translation.x = exit_portal_transform.translation.x;
+        transform.translation.y = exit_portal_transform.translation.y;
+
+        // rotation from the entry portal to the exit portal
+        let rotation = exit_portal_transform.rotation * portal_transform.rotation.inverse();
+
+        if let Some(mut linear_velocity) = linear_velocity {
+            linear_velocity.0 = (rotation * linear_velocity.0.extend(0.0)).truncate();
+        }
+
+        if let Some(mut rune_effect) = rune_effect
+            && let Some(move_speed) = rune_effect.rune_effect_move_speed
+        {
+            rune_effect.rune_effect_move_speed =
+                Some((rotation * move_speed.extend(0.0)).truncate());
+        }
+
+        commands.entity(traveller_entity).insert(Teleported);
+    }
+
+    for (traveller_entity, _, _, _, teleported) in &travellers {
+        if teleported.is_some() && !travellers_in_portals.contains(&traveller_entity) {
+            commands.entity(traveller_entity).remove::<Teleported>();
+        }
+    }
+}
+
 /*
 ========================================================================================
 Input Handling
@@ -1171,18 +875,34 @@ fn calculate_sprite_size(images: &Res<Assets<Image>>, sprite: &Sprite, scale: &V
         Vec2::new(1.0, 1.0)
     };
 
-    _sprite_size = _sprite_size * scale.truncate();
+    _sprite_size *= scale.truncate();
 
     _sprite_size
 }
 
 /*
-Handles whichever action caused a Pick-Event
+Checks if a point (in world coordinates) is inside a sprite
+note: this is assuming the Sprite-Anchor is CENTER; rotation is ignored
  */
-fn handle_event_pick(
-    //excution conditions
-    player_single: Single<Entity, (With<PlayerAttemptsPick>, Without<PlayerGameOver>)>,
-    //Globals
+fn sprite_contains_point(
+    images: &Res<Assets<Image>>,
+    sprite: &Sprite,
+    global_transform: &GlobalTransform,
+    point: Vec2,
+) -> bool {
+    let sprite_size = calculate_sprite_size(images, sprite, &global_transform.scale());
+    trace!("sprite size {:?}", sprite_size);
+
+    let sprite_center = global_transform.translation().truncate();
+
+    (point - sprite_center).abs().cmplt(sprite_size * 0.5).all()
+}
+
+/*
+Handles whichever action caused a Pick-Event
+ */
+fn handle_event_pick(
+    //Globals
     mut commands: Commands,
     images: Res<Assets<Image>>,
     //Event readers
@@ -1202,11 +922,6 @@ fn handle_event_pick(
     >,
 ) {
     trace!("Pick event processing");
-    let player_entity = player_single.into_inner();
-
-    commands
-        .entity(player_entity)
-        .remove::<PlayerAttemptsPick>();
 
     let (camera, camera_transform) = camera_q.single().ok().unwrap();
 
@@ -1234,21 +949,10 @@ fn handle_event_pick(
         note note: it seems bevy currently has no elegant way to find the actual sprite size, so I need to use this monstrosity
          */
 
-        let sprite_size = calculate_sprite_size(&images, sprite, &global_transform.scale());
-        trace!("sprite size {:?}", sprite_size);
-
-        // note: this is assuming the Sprite-Anchor is CENTER
-        if event_location_in_world.x > global_transform.translation().x - sprite_size.x * 0.5
-            && event_location_in_world.x < global_transform.translation().x + sprite_size.x * 0.5
-            && event_location_in_world.y > global_transform.translation().y - sprite_size.y * 0.5
-            && event_location_in_world.y < global_transform.translation().y + sprite_size.y * 0.5
-        {
+        if sprite_contains_point(&images, sprite, global_transform, event_location_in_world) {
             // mark as picked
             commands.entity(pickable_entity).insert(Picked);
 
-            // rmeove players ability to pick objects to prevent bugs from "double input" (probably an edge case, but hey, it's cheap to handle)
-            commands.entity(player_entity).remove::<PlayerCanPick>();
-
             //remove rune from any parent (which would be a rune slot)
             if let Some(child_of) = child_of {
                 commands
@@ -1272,24 +976,16 @@ Handles the release of whichever Action caused a previous pick event
 Release event is ignored if no Picked object was found
  */
 fn handle_event_release(
-    // Execution conditions
-    player_single: Single<Entity, (With<Player>, With<PlayerAttemptsRelease>)>,
     // Globals
     mut commands: Commands,
-    mut _release_event_reader: EventReader<ReleaseEvent>,
+    mut release_event_reader: EventReader<ReleaseEvent>,
     // Queries
     picked: Option<Single<(Entity, &mut Transform), With<Picked>>>,
 ) {
     trace!("Release event processing");
 
-    let player_entity = player_single.into_inner();
-
-    commands
-        .entity(player_entity)
-        .remove::<PlayerAttemptsRelease>();
-
-    // add PlayerCanPick marker to player
-    commands.entity(player_entity).insert(PlayerCanPick);
+    // the release location is not needed (yet), the event only signals the release
+    release_event_reader.clear();
 
     if let Some(picked_single) = picked {
         let (picked_entity, _transform) = picked_single.into_inner();
@@ -1301,18 +997,13 @@ fn handle_event_release(
 }
 
 fn controls(
-    // Execution conditions
-    player_single: Single<Entity, (With<Player>, Without<PlayerWaitingForMachine>)>,
     //Globals
-    mut commands: Commands,
     input: Res<ButtonInput<MouseButton>>,
     mut release_event_writer: EventWriter<ReleaseEvent>,
     mut pick_event_writer: EventWriter<PickEvent>,
     // Queries
     windows: Query<&Window>,
 ) {
-    let player_entity = player_single.into_inner();
-
     // Press Left Mouse
     if input.just_pressed(MouseButton::Left) {
         trace!("Left pressed");
@@ -1322,7 +1013,6 @@ fn controls(
         pick_event_writer.write(PickEvent {
             _location_in_screen_coordinates: window.cursor_position().unwrap(),
         });
-        commands.entity(player_entity).insert(PlayerAttemptsPick);
     }
 
     // Release Left Mouse
@@ -1334,7 +1024,6 @@ fn controls(
         release_event_writer.write(ReleaseEvent {
             _location_in_screen_coordinates: window.cursor_position().unwrap_or_default(),
         });
-        commands.entity(player_entity).insert(PlayerAttemptsRelease);
     }
 
     /*
@@ -1352,6 +1041,189 @@ fn controls(
     */
 }
 
+/*
+Lets the player aim adjustable ball firing thingies while building
+mouse wheel over the thingy rotates it, right click cycles through the launch power levels
+ */
+fn handle_ball_firing_thingy_aiming(
+    // Globals
+    images: Res<Assets<Image>>,
+    input: Res<ButtonInput<MouseButton>>,
+    mut mouse_wheel_event_reader: EventReader<MouseWheel>,
+    // Queries
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    ball_firing_thingies: Query<(
+        &mut BallFiringThingy,
+        &mut AdjustableBallFiringThingy,
+        &mut Transform,
+        &GlobalTransform,
+        &Sprite,
+    )>,
+) {
+    // sum up all wheel steps of this frame; direction is all that matters
+    let wheel_steps: f32 = mouse_wheel_event_reader
+        .read()
+        .map(|mouse_wheel_event| mouse_wheel_event.y.signum())
+        .sum();
+
+    let power_level_change_requested = input.just_pressed(MouseButton::Right);
+
+    if wheel_steps == 0.0 && !power_level_change_requested {
+        return;
+    }
+
+    let Some(cursor_position) = windows
+        .single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+
+    let (camera, camera_transform) = camera_q.single().ok().unwrap();
+
+    let Ok(cursor_position_in_world) =
+        camera.viewport_to_world_2d(camera_transform, cursor_position)
+    else {
+        return;
+    };
+
+    for (mut ball_firing_thingy, mut adjustable, mut transform, global_transform, sprite) in
+        ball_firing_thingies
+    {
+        if !sprite_contains_point(&images, sprite, global_transform, cursor_position_in_world) {
+            continue;
+        }
+
+        if wheel_steps != 0.0 {
+            transform.rotate_z(wheel_steps * adjustable.rotation_step);
+            trace!("ball firing thingy rotated to {:?}", transform.rotation);
+        }
+
+        if power_level_change_requested && !adjustable.power_levels.is_empty() {
+            adjustable.power_level = (adjustable.power_level + 1) % adjustable.power_levels.len();
+            ball_firing_thingy.firing_speed = adjustable.power_levels[adjustable.power_level];
+            trace!(
+                "ball firing thingy power set to {}",
+                ball_firing_thingy.firing_speed
+            );
+        }
+    }
+}
+
+/*
+Stretches the arrow of adjustable ball firing thingies according to the chosen power level
+(the aim is shown automatically, the arrow is a child and rotates with the thingy)
+ */
+fn update_ball_firing_thingy_arrows(
+    // Queries
+    ball_firing_thingies: Query<
+        (&AdjustableBallFiringThingy, &Children),
+        Changed<AdjustableBallFiringThingy>,
+    >,
+    mut arrows: Query<&mut Transform, With<BallFiringThingyArrow>>,
+) {
+    for (adjustable, children) in ball_firing_thingies {
+        let power_ratio = if adjustable.power_levels.len() > 1 {
+            adjustable.power_level as f32 / (adjustable.power_levels.len() - 1) as f32
+        } else {
+            1.0
+        };
+
+        let arrow_length = ARROW_MIN_SCALE + (ARROW_MAX_SCALE - ARROW_MIN_SCALE) * power_ratio;
+
+        for child in children {
+            if let Ok(mut arrow_transform) = arrows.get_mut(*child) {
+                arrow_transform.scale.x = arrow_length;
+            }
+        }
+    }
+}
+
+/*
+Pauses/resumes the game when Escape is pressed; only possible while building or while the machine is running
+ */
+fn toggle_pause(
+    // Globals
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    game_state: Res<State<GameState>>,
+    paused_from: Option<Res<PausedFrom>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if !input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+
+    match game_state.get() {
+        GameState::Building | GameState::MachineRunning => {
+            commands.insert_resource(PausedFrom(*game_state.get()));
+            next_game_state.set(GameState::Paused);
+        }
+        GameState::Paused => {
+            if let Some(paused_from) = paused_from {
+                next_game_state.set(paused_from.0);
+            }
+        }
+        _ => {}
+    }
+}
+
+/*
+Freezes time (which also freezes physics) and darkens the screen
+ */
+fn pause_game(
+    // Globals
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    // Queries
+    picked: Query<Entity, With<Picked>>,
+) {
+    time.pause();
+
+    // whatever the player is dragging is dropped, the release would be missed otherwise
+    for picked_entity in picked {
+        commands
+            .entity(picked_entity)
+            .remove::<Picked>()
+            .insert(Placed);
+    }
+
+    commands.spawn((
+        PauseOverlay,
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(PAUSE_OVERLAY_BLACK),
+    ));
+
+    info!("Game paused");
+}
+
+/*
+Unfreezes time and removes the pause overlay
+ */
+fn resume_game(
+    // Globals
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    // Queries
+    pause_overlays: Query<Entity, With<PauseOverlay>>,
+) {
+    time.unpause();
+
+    for pause_overlay in pause_overlays {
+        commands.entity(pause_overlay).despawn();
+    }
+
+    commands.remove_resource::<PausedFrom>();
+
+    info!("Game resumed");
+}
+
 /*
 ========================================================================================
 Event Handling
@@ -1362,51 +1234,45 @@ Event Handling
 Handles Event written when TriggerStars are activated
  */
 fn handle_event_trigger_star_activated(
-    // Execution conditions
-    player_single: Single<
-        Entity,
-        (
-            With<Player>,
-            With<PlayerWaitingForMachine>,
-            Without<DonutCircle>,
-            Without<RuneSlot>,
-            Without<Rune>,
-        ),
-    >,
     // Globals
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut next_game_state: ResMut<NextState<GameState>>,
     mut trigger_event_reader: EventReader<TriggerStarActivatedEvent>,
     mut machine_failed_write: EventWriter<MachineFailedEvent>,
+    mut machine_reset_writer: EventWriter<MachineResetEvent>,
+    machine_run: Res<MachineRun>,
+    level_scoring: Res<LevelScoring>,
+    level_stats: Res<LevelStats>,
     // Queries
     magic_donut_circle: Single<
         (Entity, &DonutCircle, &Transform),
-        (Without<Player>, Without<RuneSlot>, Without<Rune>),
+        (Without<RuneSlot>, Without<Rune>),
     >,
+    // note: an empty rune slot has no Children
     rune_slots: Query<
-        (&ChildOf, &Children),
-        (
-            With<RuneSlot>,
-            Without<Player>,
-            Without<DonutCircle>,
-            Without<Rune>,
-        ),
+        (&ChildOf, Option<&Children>),
+        (With<RuneSlot>, Without<DonutCircle>, Without<Rune>),
     >,
-    runes: Query<(Entity, &Rune), (Without<Player>, Without<DonutCircle>, Without<RuneSlot>)>,
+    runes: Query<(Entity, &Rune), (Without<DonutCircle>, Without<RuneSlot>)>,
+    chain_nodes: Query<(), Or<(With<Gate>, With<DelayNode>, With<MetronomeNode>)>>,
+    test_balls: Query<(), (With<GreyBall>, Without<FinalBall>, Without<BlueBall>)>,
 ) {
     // if the next effect is the rune circle -> summon donut and be happy
+    // note: one event per follow up entity of the activated star
     for trigger_event in trigger_event_reader.read() {
         let mut trigger_handled: bool = false;
 
+        // gates and timing nodes are handled by their own plugins (GatePlugin, TimingPlugin)
+        if chain_nodes.contains(trigger_event.entity_to_be_triggered) {
+            continue;
+        }
+
         // handle activation of donut cirle
         if trigger_event
             .entity_to_be_triggered
             .eq(&magic_donut_circle.0)
         {
-            commands
-                .entity(player_single.entity())
-                .remove::<PlayerWaitingForMachine>();
-
             // spawn donut base
             let mut donut_base_sprite =
                 Sprite::from_image(asset_server.load("Donuts/PNG/donut_1.png"));
@@ -1415,15 +1281,14 @@ fn handle_event_trigger_star_activated(
             let mut render_layer = DONUT_BASE_RENDER_LAYER;
 
             commands.spawn((
-                DonutCircle,
+                SummonedDonut,
+                LevelEntity,
                 Transform::from_xyz(
                     magic_donut_circle.2.translation.x,
                     magic_donut_circle.2.translation.y,
                     render_layer,
                 ),
-                RenderLayer {
-                    render_layer: render_layer,
-                },
+                RenderLayer { render_layer },
                 donut_base_sprite,
             ));
 
@@ -1435,15 +1300,14 @@ fn handle_event_trigger_star_activated(
             render_layer = DONUT_FROSTING_RENDER_LAYER;
 
             commands.spawn((
-                DonutCircle,
+                SummonedDonut,
+                LevelEntity,
                 Transform::from_xyz(
                     magic_donut_circle.2.translation.x,
                     magic_donut_circle.2.translation.y,
                     render_layer,
                 ),
-                RenderLayer {
-                    render_layer: render_layer,
-                },
+                RenderLayer { render_layer },
                 donut_frosting_sprite,
             ));
 
@@ -1455,63 +1319,129 @@ fn handle_event_trigger_star_activated(
             render_layer = DONUT_SPRINKLES_RENDER_LAYER;
 
             commands.spawn((
-                DonutCircle,
+                SummonedDonut,
+                LevelEntity,
                 Transform::from_xyz(
                     magic_donut_circle.2.translation.x,
                     magic_donut_circle.2.translation.y,
                     render_layer,
                 ),
-                RenderLayer {
-                    render_layer: render_layer,
-                },
+                RenderLayer { render_layer },
                 donut_sprinkles_sprite,
             ));
 
-            info!("Congratulations!");
-            commands
-                .entity(player_single.entity())
-                .insert(PlayerGameOver);
-            // game is completed
+            if machine_run.final_run {
+                let runes_used = rune_slots
+                    .iter()
+                    .filter(|(_, children)| {
+                        children.is_some_and(|children| {
+                            children.iter().any(|child| runes.contains(child))
+                        })
+                    })
+                    .count() as u32;
+
+                commands.insert_resource(LevelScore::new(
+                    &level_scoring,
+                    &level_stats,
+                    runes_used,
+                    test_balls.iter().count() as u32,
+                ));
+
+                info!("Congratulations!");
+                next_game_state.set(GameState::Won);
+            } else {
+                // a test run only shows that the machine works; the donut stays until the next ball is launched
+                info!("Test run succeeded, the final ball decides the level");
+                machine_reset_writer.write(MachineResetEvent);
+                next_game_state.set(GameState::Building);
+            }
+
+            // the run is over
             break;
         }
 
         // handle activation of rune-slot
-        if rune_slots.get(trigger_event.entity_to_be_triggered).is_ok() {
-            if let Some(rune_slot) = rune_slots.get(trigger_event.entity_to_be_triggered).ok() {
-                // find rune in query that is child of rune slot
-                for child in rune_slot.1 {
-                    if runes.contains(*child) {
-                        commands
-                            .entity(rune_slot.0.0)
-                            .insert(runes.get(*child).ok().unwrap().1.rune_effect.clone());
-                        trigger_handled = true;
-                        break;
-                    }
+        if let Ok(rune_slot) = rune_slots.get(trigger_event.entity_to_be_triggered) {
+            // find rune in query that is child of rune slot
+            for child in rune_slot.1.into_iter().flatten() {
+                if runes.contains(*child) {
+                    commands
+                        .entity(rune_slot.0.0)
+                        .insert(runes.get(*child).ok().unwrap().1.rune_effect);
+                    trigger_handled = true;
+                    break;
                 }
             }
         }
 
-        if trigger_handled {
-            break;
-        } else {
+        // every event is handled on its own, other branches of the chain may be triggered in the same frame
+        if !trigger_handled {
             // this means a star was activated, but the trigger wasn't handled -> machine has failed -> fire a reset event
             info!("Rune-Goldberg-Donut-Machine has failed, resetting game");
-            machine_failed_write.write(MachineFailedEvent);
+
+            let reason = if rune_slots.contains(trigger_event.entity_to_be_triggered) {
+                MachineFailureReason::EmptyRuneSlot {
+                    rune_slot: trigger_event.entity_to_be_triggered,
+                    triggered_by: trigger_event.triggered_by,
+                }
+            } else {
+                MachineFailureReason::NothingToTrigger {
+                    entity: trigger_event.entity_to_be_triggered,
+                    triggered_by: trigger_event.triggered_by,
+                }
+            };
+
+            machine_failed_write.write(MachineFailedEvent { reason });
         }
     }
 }
 
 /*
-Handles the event of the machine failing and resets everything
+Handles the event of the machine failing: the final ball (or the last ball) failing loses the level, otherwise the player can try again
 */
 fn handle_event_machine_failed(
-    // Execution condition
-    player_single: Single<Entity, (With<Player>, With<PlayerWaitingForMachine>)>,
     // Globals
-    mut commands: Commands,
+    mut next_game_state: ResMut<NextState<GameState>>,
     mut machine_failed_event_reader: EventReader<MachineFailedEvent>,
+    mut machine_reset_writer: EventWriter<MachineResetEvent>,
+    machine_run: Res<MachineRun>,
     // Queries
-    grey_balls: Query<&GreyBall>,
+    // note: the ball of this run keeps its GreyBall component while it is flying as blue ball
+    grey_balls: Query<(), (With<GreyBall>, Without<BlueBall>)>,
+    // note: donuts of earlier test runs are gone once the ball is launched -> summoned during this run
+    summoned_donuts: Query<(), With<SummonedDonut>>,
+) {
+    // multiple failures in the same frame still only need a single reset
+    if machine_failed_event_reader.read().last().is_none() {
+        return;
+    }
+
+    // the donut has been summoned in the same physics step; the run is over already, and it has succeeded
+    if !summoned_donuts.is_empty() {
+        debug!("Machine failure ignored, the donut has been summoned");
+        return;
+    }
+
+    if machine_run.final_run || grey_balls.is_empty() {
+        info!("Game Over!");
+        next_game_state.set(GameState::Lost);
+    } else {
+        // back to building for the next attempt
+        next_game_state.set(GameState::Building);
+    }
+
+    machine_reset_writer.write(MachineResetEvent);
+}
+
+/*
+Resets everything after a run, so the player can build again
+*/
+fn handle_event_machine_reset(
+    // Globals
+    mut commands: Commands,
+    mut machine_reset_event_reader: EventReader<MachineResetEvent>,
+    // Queries
+    blue_balls: Query<Entity, With<BlueBall>>,
     mut runes: Query<
         (
             &mut Rune,
@@ -1521,43 +1451,18 @@ fn handle_event_machine_failed(
             &ChildOf,
             Entity,
         ),
-        (Without<StarTrigger>, Without<Card>, Without<Letter>),
-    >,
-    mut card: Single<
-        (&mut Transform, &RenderLayer),
-        (
-            With<Card>,
-            With<MachinePart>,
-            Without<Rune>,
-            Without<Letter>,
-        ),
-    >,
-    mut letter: Single<
-        (&mut Transform, &RenderLayer),
-        (
-            With<Letter>,
-            With<MachinePart>,
-            Without<Rune>,
-            Without<Card>,
-        ),
+        (Without<StarTrigger>, Without<MachinePart>),
     >,
     mut trigger_stars: Query<&mut Sprite, With<StarTrigger>>,
 ) {
-    for _machine_failed_event in machine_failed_event_reader.read() {
-        commands
-            .entity(player_single.entity())
-            .remove::<PlayerWaitingForMachine>();
-
-        if grey_balls.is_empty() {
-            info!("Game Over!");
-            commands
-                .entity(player_single.entity())
-                .insert(PlayerGameOver);
-            //TODO maybe do some more than a log for game over
+    for _machine_reset_event in machine_reset_event_reader.read() {
+        // the ball of this run is used up, even if it is still flying around (e.g. off screen)
+        for blue_ball in blue_balls {
+            commands.entity(blue_ball).despawn();
         }
 
-        // reset all rune slots, runes, cards, trigger stars
-        //note: this could & should be done ina  much more generic way, but especially during this GameJam I don't have the time for fancy things like thinking before acting xP
+        // reset all rune slots, runes, trigger stars
+        // note: machine parts are restored from the snapshot taken when the machine was started (see snapshot.rs)
 
         // reset runes by restoring normal color and placing them in their default position
         for mut rune in &mut runes {
@@ -1573,30 +1478,47 @@ fn handle_event_machine_failed(
             trace!("rune reset to {:?}", rune.2.translation);
         }
 
-        // copied start translations from setup function
-        letter.0.translation = Vec3::new(-500.0, -50.0, letter.1.render_layer);
-        card.0.translation = Vec3::new(350.0, -100.0, card.1.render_layer);
-
         for mut trigger_star in &mut trigger_stars {
             trigger_star.color = Color::WHITE;
         }
     }
 }
 
+/*
+Removes the donut summoned by a successful test run
+ */
+fn despawn_summoned_donuts(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    summoned_donuts: Query<Entity, With<SummonedDonut>>,
+) {
+    for summoned_donut in summoned_donuts {
+        commands.entity(summoned_donut).despawn();
+    }
+}
+
 /*
 ========================================================================================
 Game Logic
 ========================================================================================
  */
+
+/*
+Applies a pending state change before the physics step, instead of waiting for the next frame
+otherwise a frame with several physics steps would keep running the machine after it has been won or has failed
+ */
+fn apply_game_state_transition(world: &mut World) {
+    let _ = world.try_run_schedule(StateTransition);
+}
+
 fn apply_rune_effects(
     //Globals
-    time: Res<Time>,
+    time: Res<Time<Fixed>>,
     //Queries
-    active_entities: Query<(&RuneEffect, &mut Transform)>,
+    active_entities: Query<(&mut RuneEffect, &mut Transform)>,
 ) {
-    for (rune_effect, mut transform) in active_entities {
-        // TODO actually do things here
-
+    for (mut rune_effect, mut transform) in active_entities {
         match rune_effect.rune_effect_type {
             RuneEffectType::MoveUp
             | RuneEffectType::MoveDown
@@ -1609,7 +1531,246 @@ fn apply_rune_effects(
                     warn!("Effect is movement, but no move speed was set!");
                 }
             }
+            RuneEffectType::Rotate | RuneEffectType::Spin => {
+                let Some(rotation_speed) = rune_effect.rune_effect_rotation_speed else {
+                    warn!("Effect is rotation, but no rotation speed was set!");
+                    continue;
+                };
+
+                let mut angle = rotation_speed * time.delta_secs();
+
+                if matches!(rune_effect.rune_effect_type, RuneEffectType::Rotate) {
+                    let Some(rotation_angle) = rune_effect.rune_effect_rotation_angle else {
+                        warn!("Effect is rotation, but no rotation angle was set!");
+                        continue;
+                    };
+
+                    // the target angle decides the direction, just like the scale target does for Scale
+                    // don't overshoot the target angle
+                    let remaining_angle = rotation_angle.abs() - rune_effect.rotated_angle;
+                    if remaining_angle <= 0.0 {
+                        continue;
+                    }
+                    angle = angle.abs().min(remaining_angle) * rotation_angle.signum();
+                }
+
+                rune_effect.rotated_angle += angle.abs();
+
+                // the pivot rotates and scales with the entity
+                let pivot = rune_effect
+                    .rune_effect_pivot
+                    .map_or(transform.translation, |pivot| {
+                        transform.transform_point(pivot.extend(0.0))
+                    });
+
+                transform.rotate_around(pivot, Quat::from_rotation_z(angle.to_radians()));
+            }
+            RuneEffectType::Scale => {
+                let (Some(scale_speed), Some(scale_target)) = (
+                    rune_effect.rune_effect_scale_speed,
+                    rune_effect.rune_effect_scale_target,
+                ) else {
+                    warn!("Effect is scaling, but no scale speed or target was set!");
+                    continue;
+                };
+
+                // leave the transform untouched once the target is reached, so the part counts as standing still
+                if transform.scale.x == scale_target {
+                    continue;
+                }
+
+                // the target decides whether the entity grows or shrinks, the speed only how fast
+                // don't overshoot the target scale
+                let scale_step = scale_speed.abs() * time.delta_secs();
+                let scale = if scale_target > transform.scale.x {
+                    (transform.scale.x + scale_step).min(scale_target)
+                } else {
+                    (transform.scale.x - scale_step).max(scale_target)
+                };
+
+                // z is left alone, it is used for the render layers
+                transform.scale.x = scale;
+                transform.scale.y = scale;
+            }
+            RuneEffectType::Gravity => {
+                // nothing to do every frame, physics takes over (see apply_gravity_rune_effects)
+            }
+            RuneEffectType::Teleport => {
+                // happens once, when the effect is applied (see apply_teleport_rune_effects)
+            }
+            RuneEffectType::Impulse => {
+                // happens once, when the effect is applied (see apply_impulse_rune_effects)
+            }
+        }
+    }
+}
+
+/*
+Turns entities hit by a gravity rune into dynamic bodies, so they fall (or rise) according to the rune
+ */
+fn apply_gravity_rune_effects(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    rune_effects: Query<(Entity, &RuneEffect), Changed<RuneEffect>>,
+) {
+    for (entity, rune_effect) in rune_effects {
+        if !matches!(rune_effect.rune_effect_type, RuneEffectType::Gravity) {
+            continue;
         }
+
+        let Some(rune_gravity) = rune_effect.rune_effect_gravity else {
+            warn!("Effect is gravity, but no gravity was set!");
+            continue;
+        };
+
+        // GravityScale only scales along the world gravity, sideways gravity is applied separately
+        let (gravity_scale, sideways_gravity_direction) = match rune_gravity {
+            RuneGravity::Off => (0.0, None),
+            RuneGravity::Normal => (1.0, None),
+            RuneGravity::Inverted => (-1.0, None),
+            RuneGravity::Double => (2.0, None),
+            RuneGravity::Left => (0.0, Some(Vec2::NEG_X)),
+            RuneGravity::Right => (0.0, Some(Vec2::X)),
+        };
+
+        let mut entity_commands = commands.entity(entity);
+
+        entity_commands.insert((RigidBody::Dynamic, GravityScale(gravity_scale)));
+
+        match sideways_gravity_direction {
+            Some(direction) => entity_commands.insert(SidewaysGravity { direction }),
+            None => entity_commands.remove::<SidewaysGravity>(),
+        };
+
+        debug!("gravity of {} set to {:?}", entity, rune_gravity);
+    }
+}
+
+/*
+Moves entities hit by a teleport rune to the teleport target of the rune slot the rune was placed in
+the RuneEffect stays on the entity, so it still activates a trigger star it is teleported onto
+the direction of movement is turned by the rotation of the target relative to the rune slot, just like with portals
+ */
+fn apply_teleport_rune_effects(
+    // Queries
+    mut rune_effects: Query<
+        (
+            Entity,
+            &mut RuneEffect,
+            &mut Transform,
+            Option<&mut LinearVelocity>,
+        ),
+        Changed<RuneEffect>,
+    >,
+    rune_slots: Query<(Entity, &RuneSlot, &ChildOf)>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    for (entity, mut rune_effect, mut transform, linear_velocity) in &mut rune_effects {
+        if !matches!(rune_effect.rune_effect_type, RuneEffectType::Teleport) {
+            continue;
+        }
+
+        let Some((rune_slot_entity, teleport_target)) = rune_slots
+            .iter()
+            .find(|(_, _, child_of)| child_of.parent().eq(&entity))
+            .and_then(|(rune_slot_entity, rune_slot, _)| {
+                rune_slot
+                    .teleport_target
+                    .map(|teleport_target| (rune_slot_entity, teleport_target))
+            })
+        else {
+            warn!("Effect is teleport, but the rune slot has no teleport target!");
+            continue;
+        };
+
+        let Ok(teleport_target_transform) = global_transforms.get(teleport_target) else {
+            warn!("Teleport target {} does not exist", teleport_target);
+            continue;
+        };
+
+        let target_position = teleport_target_transform.translation();
+        transform.translation.x = target_position.x;
+        transform.translation.y = target_position.y;
+
+        // rotation from the rune slot to the teleport target
+        let rune_slot_rotation = global_transforms
+            .get(rune_slot_entity)
+            .map_or(Quat::IDENTITY, GlobalTransform::rotation);
+        let rotation = teleport_target_transform.rotation() * rune_slot_rotation.inverse();
+
+        if let Some(mut linear_velocity) = linear_velocity {
+            linear_velocity.0 = (rotation * linear_velocity.0.extend(0.0)).truncate();
+        }
+
+        // note: without change detection, the effect must not be applied again
+        if let Some(move_speed) = rune_effect.rune_effect_move_speed {
+            rune_effect.bypass_change_detection().rune_effect_move_speed =
+                Some((rotation * move_speed.extend(0.0)).truncate());
+        }
+
+        debug!("{} teleported to {:?}", entity, target_position);
+    }
+}
+
+/*
+Kicks entities hit by an impulse rune; from then on physics moves them (momentum, bouncing, collisions)
+entities that are not dynamic bodies yet become one, without gravity
+ */
+fn apply_impulse_rune_effects(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    mut rune_effects: Query<
+        (
+            Entity,
+            &RuneEffect,
+            Option<&RigidBody>,
+            Option<&mut LinearVelocity>,
+        ),
+        Changed<RuneEffect>,
+    >,
+) {
+    for (entity, rune_effect, rigid_body, linear_velocity) in &mut rune_effects {
+        if !matches!(rune_effect.rune_effect_type, RuneEffectType::Impulse) {
+            continue;
+        }
+
+        let Some(impulse) = rune_effect.rune_effect_impulse else {
+            warn!("Effect is impulse, but no impulse was set!");
+            continue;
+        };
+
+        match (rigid_body, linear_velocity) {
+            (Some(rigid_body), Some(mut linear_velocity)) if rigid_body.is_dynamic() => {
+                linear_velocity.0 += impulse;
+            }
+            _ => {
+                commands.entity(entity).insert((
+                    RigidBody::Dynamic,
+                    GravityScale(0.0),
+                    LinearVelocity(impulse),
+                ));
+            }
+        }
+
+        debug!("{} kicked with {:?}", entity, impulse);
+    }
+}
+
+/*
+Accelerates entities with sideways gravity just like the world gravity would, only in their direction
+once per physics step, so the result doesn't depend on the frame rate
+ */
+fn apply_sideways_gravity(
+    //Globals
+    time: Res<Time<Fixed>>,
+    gravity: Res<Gravity>,
+    //Queries
+    sideways_gravity_entities: Query<(&SidewaysGravity, &mut LinearVelocity)>,
+) {
+    for (sideways_gravity, mut linear_velocity) in sideways_gravity_entities {
+        linear_velocity.0 += sideways_gravity.direction * gravity.0.length() * time.delta_secs();
     }
 }
 
@@ -1640,13 +1801,6 @@ marks a an object as 'Picked', meaning the player is dragging it
 #[derive(Component)]
 struct Picked;
 
-/*
-marks the player entity
-the player entity holds marker components for game logic
- */
-#[derive(Component)]
-struct Player;
-
 /*
 Each Rendered Object can be queried along with it's render layer
 
@@ -1661,70 +1815,169 @@ struct RenderLayer {
 }
 
 /*
-should be added to player Component when a Picked entity is released
-is used to make sure Pick-Events are only handled while the player holds this tag
+Marker for Cards
  */
 #[derive(Component)]
-struct PlayerCanPick;
-
+struct Card;
 /*
-marks that the player is attempting a pick
+Marker for Letters
  */
 #[derive(Component)]
-struct PlayerAttemptsPick;
+struct Letter;
 
-/*
-Marks that the player is waiting for the machine to complete
- */
 #[derive(Component)]
-struct PlayerWaitingForMachine;
+struct DonutCircle;
 
 /*
-marks that the player is attempting a release
+Marks the parts of the donut that was summoned by the machine
  */
 #[derive(Component)]
-struct PlayerAttemptsRelease;
+struct SummonedDonut;
 
-/*
-Game over marker
- */
 #[derive(Component)]
-struct PlayerGameOver;
+struct BlueBall;
+
+#[derive(Component)]
+struct GreyBall {
+    default_position: Vec2,
+}
 
 /*
-Marker for Cards
+Marks the final ball of a level; all other balls are test balls
+a test run shows whether the machine works, but only the final run decides the level (all or nothing)
  */
 #[derive(Component)]
-struct Card;
+struct FinalBall;
+
 /*
-Marker for Letters
+default direction of BallFiringThingy is to the left; rotating its transform rotates the firing direction
  */
 #[derive(Component)]
-struct Letter;
+struct BallFiringThingy {
+    // speed is units per second; see addition of physics plugin to determine how much that is in pixels
+    firing_speed: f32,
+}
+
+impl BallFiringThingy {
+    fn firing_velocity(&self, transform: &Transform) -> Vec2 {
+        (transform.rotation * Vec3::NEG_X).truncate() * self.firing_speed
+    }
+}
 
+/*
+Ball firing thingies with this component can be aimed by the player
+ */
 #[derive(Component)]
-struct DonutCircle;
+struct AdjustableBallFiringThingy {
+    // in radians per mouse wheel step
+    rotation_step: f32,
+    power_levels: Vec<f32>,
+    // index into power_levels
+    power_level: usize,
+}
 
+/*
+Marks the arrow that shows the aim of a ball firing thingy
+ */
 #[derive(Component)]
-struct BlueBall;
+struct BallFiringThingyArrow;
 
 #[derive(Component)]
-struct GreyBall {
-    default_position: Vec2,
+struct RuneSlot {
+    // where a teleport rune in this slot sends the entity the slot is attached to
+    teleport_target: Option<Entity>,
 }
 
+/*
+Whatever enters a portal (blue ball, entities affected by RuneEffects) comes out of the linked portal
+ */
 #[derive(Component)]
-struct BallFiringThingy {
-    firing_direction: Vec2,
+struct Portal {
+    linked_portal: Option<Entity>,
 }
 
+/*
+Marks entities that have just come out of a portal, so they are not sent straight back
+removed once they no longer touch any portal
+ */
 #[derive(Component)]
-struct RuneSlot;
+struct Teleported;
 
-#[derive(Component, Default, Copy, Clone)]
+/*
+Which parameters are needed depends on the effect type:
+Move* -> rune_effect_move_speed
+Rotate -> rune_effect_rotation_speed, rune_effect_rotation_angle, rune_effect_pivot (optional)
+Spin -> rune_effect_rotation_speed, rune_effect_pivot (optional)
+Scale -> rune_effect_scale_speed, rune_effect_scale_target
+Gravity -> rune_effect_gravity
+Teleport -> teleport target of the rune slot (see RuneSlot)
+Impulse -> rune_effect_impulse
+ */
+#[derive(Component, Default, Copy, Clone, Serialize, Deserialize)]
 struct RuneEffect {
     rune_effect_type: RuneEffectType,
+    #[serde(default)]
     rune_effect_move_speed: Option<Vec2>,
+    // in degrees per second, counter-clockwise; negative values rotate clockwise (Rotate only uses the amount)
+    #[serde(default)]
+    rune_effect_rotation_speed: Option<f32>,
+    // in degrees, counter-clockwise; negative values rotate clockwise
+    // Rotate stops once the part has been rotated this far (e.g. a lever swinging into a star)
+    #[serde(default)]
+    rune_effect_rotation_angle: Option<f32>,
+    // point to rotate around, relative to the center of the affected entity (unrotated); rotates around the center if not set
+    #[serde(default)]
+    rune_effect_pivot: Option<Vec2>,
+    // change of the scale factor per second; whether the entity grows or shrinks depends on the target
+    #[serde(default)]
+    rune_effect_scale_speed: Option<f32>,
+    // Scale stops once the entity has reached this scale factor
+    #[serde(default)]
+    rune_effect_scale_target: Option<f32>,
+    // the entity becomes a dynamic body with this gravity; stays that way until the machine is reset
+    #[serde(default)]
+    rune_effect_gravity: Option<RuneGravity>,
+    // change of velocity in pixels per second; the entity becomes a dynamic body if it isn't one already
+    #[serde(default)]
+    rune_effect_impulse: Option<Vec2>,
+    // how far (in degrees) the entity has been rotated by this effect so far
+    #[serde(skip)]
+    rotated_angle: f32,
+}
+
+impl RuneEffect {
+    /*
+    Whether the effect still changes the entity it is applied to (see apply_rune_effects)
+    gravity, teleport and impulse happen once, from then on physics moves the entity
+     */
+    fn is_running(&self, transform: &Transform) -> bool {
+        match self.rune_effect_type {
+            RuneEffectType::MoveUp
+            | RuneEffectType::MoveDown
+            | RuneEffectType::MoveLeft
+            | RuneEffectType::MoveRight => self
+                .rune_effect_move_speed
+                .is_some_and(|move_speed| move_speed != Vec2::ZERO),
+            RuneEffectType::Rotate => {
+                self.rune_effect_rotation_speed
+                    .is_some_and(|rotation_speed| rotation_speed != 0.0)
+                    && self
+                        .rune_effect_rotation_angle
+                        .is_some_and(|rotation_angle| self.rotated_angle < rotation_angle.abs())
+            }
+            RuneEffectType::Spin => self
+                .rune_effect_rotation_speed
+                .is_some_and(|rotation_speed| rotation_speed != 0.0),
+            RuneEffectType::Scale => {
+                self.rune_effect_scale_speed
+                    .is_some_and(|scale_speed| scale_speed != 0.0)
+                    && self
+                        .rune_effect_scale_target
+                        .is_some_and(|scale_target| transform.scale.x != scale_target)
+            }
+            RuneEffectType::Gravity | RuneEffectType::Teleport | RuneEffectType::Impulse => false,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -1737,11 +1990,19 @@ struct AddCollider {
 }
 
 /*
-Store which entity to trigger when the star is activated
+Store which entities to trigger when the star is activated; all of them are triggered at once
  */
 #[derive(Component)]
 struct StarTrigger {
-    follow_up_entity: Option<Entity>,
+    follow_up_entities: Vec<Entity>,
+}
+
+/*
+Gravity to the side for dynamic bodies; GravityScale can only scale the world gravity
+ */
+#[derive(Component, Copy, Clone)]
+struct SidewaysGravity {
+    direction: Vec2,
 }
 
 /*
@@ -1772,33 +2033,179 @@ struct ReleaseEvent {
 Event is triggered when a Trigger Star is activated
 
 Stores the entity-id of the rune-slot to be activated next OR the entity id of the holy circle of donut spawning
+OR the entity id of a gate or timing node (see gate.rs, timing.rs)
  */
 #[derive(Event)]
 struct TriggerStarActivatedEvent {
     entity_to_be_triggered: Entity,
+    // the trigger star (or gate/timing node) that was activated
+    triggered_by: Entity,
 }
 
 /*
 Event that's fired when the machine fails to continue running
  */
 #[derive(Event)]
-struct MachineFailedEvent;
+struct MachineFailedEvent {
+    reason: MachineFailureReason,
+}
+
+/*
+Event that's fired when the machine is put back into its pre-run state (after a failed run or a test run)
+ */
+#[derive(Event)]
+struct MachineResetEvent;
+
+/*
+========================================================================================
+States
+========================================================================================
+ */
+
+/*
+Game flow
+
+MainMenu -> Building (level selected)
+Building -> MachineRunning (ball launched)
+MachineRunning -> Building (machine failed, balls left) / Lost (no balls left) / Won (donut summoned)
+Won / Lost -> Building (next level / retry) or MainMenu (campaign completed)
+Building / MachineRunning <-> Paused
+MainMenu <-> Editing (level editor); Editing -> Building (test run) -> Editing
+ */
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+enum GameState {
+    #[default]
+    MainMenu,
+    Building,
+    MachineRunning,
+    Won,
+    Lost,
+    Paused,
+    Editing,
+}
+
+/*
+Whether the ball of the current run is the final one; set when a ball is launched
+ */
+#[derive(Resource)]
+struct MachineRun {
+    final_run: bool,
+}
+
+/*
+Remembers which state the game was paused in, so it can be resumed
+ */
+#[derive(Resource)]
+struct PausedFrom(GameState);
+
+/*
+Marks the overlay that darkens the screen while the game is paused
+ */
+#[derive(Component)]
+struct PauseOverlay;
 
 /*
 ========================================================================================
 Enums
 ========================================================================================
  */
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub enum ColliderType {
     Circle,
     Rectangle,
 }
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum RuneEffectType {
     #[default]
     MoveUp,
     MoveDown,
     MoveLeft,
     MoveRight,
+    // rotates around the center or a pivot, until the rotation angle is reached
+    Rotate,
+    // rotates around the center or a pivot, forever
+    Spin,
+    // scales up or down, until the scale target is reached
+    Scale,
+    // turns the entity into a dynamic body with a different gravity
+    Gravity,
+    // moves the entity to the teleport target of its rune slot, instantly
+    Teleport,
+    // kicks the entity once, physics takes over from there
+    Impulse,
+}
+
+/*
+Why the machine has failed, along with the entities involved
+ */
+#[derive(Copy, Clone, Debug)]
+enum MachineFailureReason {
+    // the blue ball hit a trigger star without hitting a rune first
+    BallMissedRunes {
+        trigger_star: Entity,
+    },
+    // a machine part hit a trigger star that has no follow up
+    DeadEndTriggerStar {
+        trigger_star: Entity,
+        machine_part: Entity,
+    },
+    // a rune slot was triggered, but there is no rune in it
+    EmptyRuneSlot {
+        rune_slot: Entity,
+        triggered_by: Entity,
+    },
+    // something was triggered that can't be triggered (e.g. an entity that isn't a rune slot)
+    NothingToTrigger {
+        entity: Entity,
+        triggered_by: Entity,
+    },
+    // a Xor gate received more than one input
+    GateConflict {
+        gate: Entity,
+    },
+    // nothing has moved for too long
+    Idle,
+    // the machine has been running for too long
+    Timeout,
+    // the blue ball or a machine part has left the play area
+    LeftPlayArea {
+        entity: Entity,
+    },
+}
+
+impl MachineFailureReason {
+    /*
+    The entities to point the player to; the culprit (e.g. the trigger star that was hit) comes first
+     */
+    fn involved_entities(&self) -> Vec<Entity> {
+        match *self {
+            MachineFailureReason::BallMissedRunes { trigger_star } => vec![trigger_star],
+            MachineFailureReason::DeadEndTriggerStar {
+                trigger_star,
+                machine_part,
+            } => vec![trigger_star, machine_part],
+            MachineFailureReason::EmptyRuneSlot {
+                rune_slot,
+                triggered_by,
+            } => vec![rune_slot, triggered_by],
+            MachineFailureReason::NothingToTrigger {
+                entity,
+                triggered_by,
+            } => vec![triggered_by, entity],
+            MachineFailureReason::GateConflict { gate } => vec![gate],
+            MachineFailureReason::Idle | MachineFailureReason::Timeout => Vec::new(),
+            MachineFailureReason::LeftPlayArea { entity } => vec![entity],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum RuneGravity {
+    Off,
+    Normal,
+    Inverted,
+    Double,
+    Left,
+    Right,
 }
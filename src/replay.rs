@@ -0,0 +1,596 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AddCollider, AdjustableBallFiringThingy, BallFiringThingy, GameState, Picked, Placed,
+    RenderLayer,
+    campaign::{CAMPAIGN_LEVELS, start_level},
+    level::{CurrentLevel, LevelEntity, LevelEntityIndex},
+    save::{read_data_file, write_data_file},
+    undo::BuildHistoryEvent,
+};
+
+/*
+========================================================================================
+Replays
+
+Everything the player does in a level is recorded with the physics step it happened in:
+- dropping a rune or a ball (a ball dropped on a ball firing thingy is a launch)
+- aiming a ball firing thingy
+- undo & redo
+Whenever a run of the machine ends, the recording is written to last.replay.ron; a won level also keeps
+its own replay (e.g. 01_first_steps.replay.ron), both next to the save
+F9 (level select or while building) plays the last replay; `--replay <file>` plays a replay file at startup
+The actions are applied in the same physics steps again, the machine itself is simulated anew
+-> the machine only advances with physics steps, so it runs the same way, no matter the frame rate
+========================================================================================
+*/
+
+const LAST_REPLAY_FILE_NAME: &str = "last.replay.ron";
+
+// physics steps a dropped entity rests at its new position before it is released, so the collisions are up to date
+const DROP_SETTLE_TICKS: u32 = 2;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayClock>()
+            .init_resource::<ReplayRecorder>()
+            .add_event::<PlayReplayEvent>()
+            .add_systems(
+                OnTransition {
+                    exited: GameState::MachineRunning,
+                    entered: GameState::Building,
+                },
+                write_replay,
+            )
+            .add_systems(
+                OnTransition {
+                    exited: GameState::MachineRunning,
+                    entered: GameState::Won,
+                },
+                write_replay,
+            )
+            .add_systems(
+                OnTransition {
+                    exited: GameState::MachineRunning,
+                    entered: GameState::Lost,
+                },
+                write_replay,
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_replay_clock,
+                    play_replay.run_if(resource_exists::<ReplayPlayback>),
+                    // note: a drop is recorded before the physics step that handles it (and removes Placed)
+                    (record_drops, record_aims, record_build_history)
+                        .run_if(in_state(GameState::Building))
+                        .run_if(not(resource_exists::<ReplayPlayback>)),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Building).or(in_state(GameState::MachineRunning)))
+                    // the level is complete once every sprite has been loaded and has its collider,
+                    // which takes a different time whenever the level is started
+                    .run_if(not(any_with_component::<AddCollider>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_level_clock.run_if(resource_exists_and_changed::<CurrentLevel>),
+                    handle_replay_key
+                        .run_if(in_state(GameState::MainMenu).or(in_state(GameState::Building))),
+                    start_replay.run_if(on_event::<PlayReplayEvent>),
+                    finish_replay.run_if(resource_exists::<ReplayPlayback>),
+                )
+                    .chain(),
+            );
+
+        #[cfg(not(target_arch = "wasm32"))]
+        request_replay_from_command_line(app);
+    }
+}
+
+/*
+Everything the player did in one attempt of a level
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Replay {
+    // as used by the campaign, e.g. levels/01_first_steps.level.ron
+    level_path: String,
+    steps: Vec<ReplayStep>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct ReplayStep {
+    // physics steps since the level was spawned (loading and pauses excluded)
+    tick: u32,
+    // the same in seconds, for humans reading the file
+    seconds: f32,
+    action: ReplayAction,
+}
+
+/*
+Level entities are referenced by their position in the level file (see LevelEntityIndex)
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum ReplayAction {
+    Drop {
+        entity: usize,
+        position: Vec2,
+    },
+    Aim {
+        entity: usize,
+        rotation: Quat,
+        power_level: usize,
+    },
+    Undo,
+    Redo,
+}
+
+/*
+Physics steps since the current level has been spawned (loading excluded)
+ */
+#[derive(Resource, Default)]
+struct ReplayClock {
+    ticks: u32,
+}
+
+#[derive(Resource, Default)]
+struct ReplayRecorder {
+    // None while no level is played (or a replay is playing)
+    replay: Option<Replay>,
+    // last recorded aim per ball firing thingy, so only changes are recorded
+    aims: HashMap<Entity, (Quat, usize)>,
+}
+
+/*
+Present while a replay is played; the player's input is ignored meanwhile
+ */
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    // the steps before have been applied
+    next_step: usize,
+    // dropped entity that is released once the clock reaches the tick
+    pending_release: Option<(Entity, u32)>,
+    // set once the replay's level has been spawned
+    level_spawned: bool,
+}
+
+#[derive(Event)]
+struct PlayReplayEvent {
+    replay: Replay,
+}
+
+/*
+Resets the clock once a level has been spawned; the recording of the level starts right away
+ */
+fn start_level_clock(
+    // Globals
+    current_level: Res<CurrentLevel>,
+    mut replay_clock: ResMut<ReplayClock>,
+    mut replay_recorder: ResMut<ReplayRecorder>,
+    replay_playback: Option<ResMut<ReplayPlayback>>,
+) {
+    if !current_level.spawned {
+        return;
+    }
+
+    replay_clock.ticks = 0;
+
+    if let Some(mut replay_playback) = replay_playback {
+        replay_playback.level_spawned = true;
+        replay_recorder.replay = None;
+        return;
+    }
+
+    let Some(level_path) = current_level.handle.path() else {
+        // e.g. a test run from the level editor
+        debug!("Level has no file, it will not be recorded");
+        return;
+    };
+
+    *replay_recorder = ReplayRecorder {
+        replay: Some(Replay {
+            level_path: level_path.to_string(),
+            steps: Vec::new(),
+        }),
+        ..default()
+    };
+}
+
+fn tick_replay_clock(
+    // Globals
+    mut replay_clock: ResMut<ReplayClock>,
+) {
+    replay_clock.ticks += 1;
+}
+
+impl ReplayRecorder {
+    fn record(&mut self, replay_clock: &ReplayClock, time: &Time<Fixed>, action: ReplayAction) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+
+        trace!("replay step recorded: {:?}", action);
+
+        replay.steps.push(ReplayStep {
+            tick: replay_clock.ticks,
+            seconds: replay_clock.ticks as f32 * time.timestep().as_secs_f32(),
+            action,
+        });
+    }
+}
+
+/*
+Records every rune or ball the player drops
+ */
+fn record_drops(
+    // Globals
+    replay_clock: Res<ReplayClock>,
+    time: Res<Time<Fixed>>,
+    mut replay_recorder: ResMut<ReplayRecorder>,
+    // Queries
+    dropped: Query<(&LevelEntityIndex, &Transform), Added<Placed>>,
+) {
+    for (level_entity_index, transform) in dropped {
+        replay_recorder.record(
+            &replay_clock,
+            &time,
+            ReplayAction::Drop {
+                entity: level_entity_index.0,
+                position: transform.translation.truncate(),
+            },
+        );
+    }
+}
+
+/*
+Records the aim of every ball firing thingy whenever it has changed
+ */
+fn record_aims(
+    // Globals
+    replay_clock: Res<ReplayClock>,
+    time: Res<Time<Fixed>>,
+    mut replay_recorder: ResMut<ReplayRecorder>,
+    // Queries
+    ball_firing_thingies: Query<(
+        Entity,
+        &LevelEntityIndex,
+        &Transform,
+        &AdjustableBallFiringThingy,
+    )>,
+) {
+    for (entity, level_entity_index, transform, adjustable) in ball_firing_thingies {
+        let aim = (transform.rotation, adjustable.power_level);
+
+        // note: the aim the level starts with is not an action
+        match replay_recorder.aims.insert(entity, aim) {
+            Some(recorded_aim) if recorded_aim != aim => {}
+            _ => continue,
+        }
+
+        replay_recorder.record(
+            &replay_clock,
+            &time,
+            ReplayAction::Aim {
+                entity: level_entity_index.0,
+                rotation: aim.0,
+                power_level: aim.1,
+            },
+        );
+    }
+}
+
+fn record_build_history(
+    // Globals
+    replay_clock: Res<ReplayClock>,
+    time: Res<Time<Fixed>>,
+    mut replay_recorder: ResMut<ReplayRecorder>,
+    mut build_history_event_reader: EventReader<BuildHistoryEvent>,
+) {
+    for build_history_event in build_history_event_reader.read() {
+        let action = match build_history_event {
+            BuildHistoryEvent::Undo => ReplayAction::Undo,
+            BuildHistoryEvent::Redo => ReplayAction::Redo,
+        };
+
+        replay_recorder.record(&replay_clock, &time, action);
+    }
+}
+
+/*
+Writes the recording whenever a run of the machine has ended
+ */
+fn write_replay(
+    // Globals
+    game_state: Res<State<GameState>>,
+    replay_recorder: Res<ReplayRecorder>,
+) {
+    let Some(replay) = &replay_recorder.replay else {
+        return;
+    };
+
+    let replay_ron = match ron::ser::to_string_pretty(replay, ron::ser::PrettyConfig::default()) {
+        Ok(replay_ron) => replay_ron,
+        Err(error) => {
+            warn!("Could not serialize replay: {}", error);
+            return;
+        }
+    };
+
+    let mut replay_file_names = vec![LAST_REPLAY_FILE_NAME.to_string()];
+
+    // e.g. levels/01_first_steps.level.ron -> 01_first_steps.replay.ron
+    if *game_state.get() == GameState::Won {
+        let level_name = replay
+            .level_path
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(".level.ron");
+        replay_file_names.push(format!("{}.replay.ron", level_name));
+    }
+
+    for replay_file_name in replay_file_names {
+        match write_data_file(&replay_file_name, &replay_ron) {
+            Ok(()) => debug!("Replay written to {}", replay_file_name),
+            Err(error) => warn!("Could not write replay: {}", error),
+        }
+    }
+}
+
+/*
+F9 plays the last replay
+ */
+fn handle_replay_key(
+    // Globals
+    input: Res<ButtonInput<KeyCode>>,
+    mut play_replay_event_writer: EventWriter<PlayReplayEvent>,
+    replay_playback: Option<Res<ReplayPlayback>>,
+) {
+    if !input.just_pressed(KeyCode::F9) || replay_playback.is_some() {
+        return;
+    }
+
+    let replay = read_data_file(LAST_REPLAY_FILE_NAME)
+        .map_err(|error| error.to_string())
+        .and_then(|replay_ron| match replay_ron {
+            Some(replay_ron) => parse_replay(&replay_ron),
+            None => Err("no replay recorded yet".to_string()),
+        });
+
+    match replay {
+        Ok(replay) => {
+            play_replay_event_writer.write(PlayReplayEvent { replay });
+        }
+        Err(error) => warn!("Could not load last replay: {}", error),
+    }
+}
+
+fn parse_replay(replay_ron: &str) -> Result<Replay, String> {
+    ron::from_str(replay_ron).map_err(|error| error.to_string())
+}
+
+/*
+`--replay <file>` plays the replay file once the game has started
+ */
+#[cfg(not(target_arch = "wasm32"))]
+fn request_replay_from_command_line(app: &mut App) {
+    let mut args = std::env::args().skip_while(|arg| arg != "--replay").skip(1);
+
+    let Some(replay_file_path) = args.next() else {
+        return;
+    };
+
+    let replay = std::fs::read_to_string(&replay_file_path)
+        .map_err(|error| error.to_string())
+        .and_then(|replay_ron| parse_replay(&replay_ron));
+
+    match replay {
+        Ok(replay) => {
+            app.world_mut().send_event(PlayReplayEvent { replay });
+        }
+        Err(error) => warn!("Could not load replay {}: {}", replay_file_path, error),
+    }
+}
+
+/*
+Starts the level of the replay; the steps are applied once it has been spawned
+ */
+fn start_replay(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut play_replay_event_reader: EventReader<PlayReplayEvent>,
+    // Queries
+    level_entities: Query<Entity, (With<LevelEntity>, Without<ChildOf>)>,
+) {
+    let Some(play_replay_event) = play_replay_event_reader.read().last() else {
+        return;
+    };
+
+    let replay = play_replay_event.replay.clone();
+
+    let Some(campaign_index) = CAMPAIGN_LEVELS
+        .iter()
+        .position(|level_path| *level_path == replay.level_path)
+    else {
+        warn!("Replay is for unknown level {}", replay.level_path);
+        return;
+    };
+
+    info!(
+        "Playing replay of {} ({} steps)",
+        replay.level_path,
+        replay.steps.len()
+    );
+
+    start_level(
+        &mut commands,
+        &asset_server,
+        &mut next_game_state,
+        &level_entities,
+        campaign_index,
+    );
+
+    commands.insert_resource(ReplayPlayback {
+        replay,
+        next_step: 0,
+        pending_release: None,
+        level_spawned: false,
+    });
+}
+
+/*
+Applies every step whose physics step has come
+ */
+fn play_replay(
+    // Globals
+    mut commands: Commands,
+    replay_clock: Res<ReplayClock>,
+    mut replay_playback: ResMut<ReplayPlayback>,
+    mut build_history_event_writer: EventWriter<BuildHistoryEvent>,
+    // Queries
+    level_entities: Query<(Entity, &LevelEntityIndex)>,
+    mut transforms: Query<(&mut Transform, Option<&RenderLayer>, Option<&ChildOf>)>,
+    mut ball_firing_thingies: Query<(&mut BallFiringThingy, &mut AdjustableBallFiringThingy)>,
+) {
+    if !replay_playback.level_spawned {
+        return;
+    }
+
+    // the dropped entity has been at its new position long enough
+    if let Some((dropped, release_tick)) = replay_playback.pending_release
+        && release_tick <= replay_clock.ticks
+    {
+        release(&mut commands, &mut replay_playback, dropped);
+    }
+
+    while let Some(replay_step) = replay_playback.replay.steps.get(replay_playback.next_step) {
+        if replay_step.tick > replay_clock.ticks {
+            break;
+        }
+
+        let action = replay_step.action.clone();
+        replay_playback.next_step += 1;
+
+        let find_entity = |level_entity_index: usize| {
+            let entity = level_entities
+                .iter()
+                .find(|(_, index)| index.0 == level_entity_index)
+                .map(|(entity, _)| entity);
+
+            if entity.is_none() {
+                warn!("Replay references unknown entity {}", level_entity_index);
+            }
+
+            entity
+        };
+
+        match action {
+            ReplayAction::Drop { entity, position } => {
+                let Some(entity) = find_entity(entity) else {
+                    continue;
+                };
+
+                // one drop at a time
+                if let Some((dropped, _)) = replay_playback.pending_release {
+                    release(&mut commands, &mut replay_playback, dropped);
+                }
+
+                let Ok((mut transform, render_layer, child_of)) = transforms.get_mut(entity) else {
+                    continue;
+                };
+
+                // picked up, just like the player would: out of its rune slot first
+                if let Some(child_of) = child_of {
+                    commands
+                        .entity(child_of.parent())
+                        .remove_children(&[entity]);
+                    transform.scale = Vec3::ONE;
+
+                    if let Some(render_layer) = render_layer {
+                        transform.translation.z = render_layer.render_layer;
+                    }
+                }
+
+                transform.translation.x = position.x;
+                transform.translation.y = position.y;
+
+                commands.entity(entity).insert(Picked);
+                replay_playback.pending_release =
+                    Some((entity, replay_clock.ticks + DROP_SETTLE_TICKS));
+            }
+            ReplayAction::Aim {
+                entity,
+                rotation,
+                power_level,
+            } => {
+                let Some(entity) = find_entity(entity) else {
+                    continue;
+                };
+
+                if let Ok((mut transform, _, _)) = transforms.get_mut(entity) {
+                    transform.rotation = rotation;
+                }
+
+                if let Ok((mut ball_firing_thingy, mut adjustable)) =
+                    ball_firing_thingies.get_mut(entity)
+                    && let Some(firing_speed) = adjustable.power_levels.get(power_level).copied()
+                {
+                    adjustable.power_level = power_level;
+                    ball_firing_thingy.firing_speed = firing_speed;
+                }
+            }
+            ReplayAction::Undo => {
+                build_history_event_writer.write(BuildHistoryEvent::Undo);
+            }
+            ReplayAction::Redo => {
+                build_history_event_writer.write(BuildHistoryEvent::Redo);
+            }
+        }
+    }
+}
+
+fn release(commands: &mut Commands, replay_playback: &mut ReplayPlayback, dropped: Entity) {
+    commands.entity(dropped).remove::<Picked>().insert(Placed);
+    replay_playback.pending_release = None;
+}
+
+/*
+Gives control back to the player once every step has been applied and the last run is over
+ */
+fn finish_replay(
+    // Globals
+    mut commands: Commands,
+    game_state: Res<State<GameState>>,
+    replay_clock: Res<ReplayClock>,
+    replay_playback: Res<ReplayPlayback>,
+) {
+    let last_tick = replay_playback
+        .replay
+        .steps
+        .last()
+        .map_or(0, |replay_step| replay_step.tick);
+
+    let all_steps_applied = replay_playback.level_spawned
+        && replay_playback.next_step == replay_playback.replay.steps.len()
+        && replay_playback.pending_release.is_none();
+
+    // note: the last drop launches the ball a few physics steps later
+    if all_steps_applied
+        && replay_clock.ticks > last_tick + DROP_SETTLE_TICKS
+        && *game_state.get() != GameState::MachineRunning
+        && *game_state.get() != GameState::Paused
+    {
+        info!("Replay finished");
+        commands.remove_resource::<ReplayPlayback>();
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use bevy::time::TimeUpdateStrategy;
+
+use super::*;
+use crate::simulation::{BallKind, MachineOutcome, Simulation};
+
+const FIRST_STEPS: &str = "levels/01_first_steps.level.ron";
+
+// a replay that is still playing after this many frames is stuck
+const MAX_PLAYBACK_FRAMES: u32 = 3000;
+
+/*
+Physics steps the machine has been running
+ */
+#[derive(Resource, Default)]
+struct MachineSteps(u32);
+
+fn count_machine_steps(
+    // Globals
+    mut machine_steps: ResMut<MachineSteps>,
+) {
+    machine_steps.0 += 1;
+}
+
+/*
+Replays on top of the headless machine; keys are never pressed and undo is not needed
+ */
+fn replays(app: &mut App) {
+    app.add_event::<BuildHistoryEvent>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<MachineSteps>()
+        .add_systems(
+            FixedUpdate,
+            count_machine_steps.run_if(in_state(GameState::MachineRunning)),
+        )
+        .add_plugins(ReplayPlugin);
+}
+
+#[test]
+fn replay_runs_the_machine_the_same_way_at_another_frame_rate() {
+    let mut recording = Simulation::with_plugins(FIRST_STEPS, replays);
+
+    assert!(recording.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(recording.launch_ball(BallKind::Final));
+    assert!(matches!(recording.run_machine(), MachineOutcome::Won));
+
+    let replay = recording
+        .world()
+        .resource::<ReplayRecorder>()
+        .replay
+        .clone()
+        .expect("the level has not been recorded");
+    let recorded_machine_steps = recording.world().resource::<MachineSteps>().0;
+
+    // the rune and the ball
+    assert_eq!(replay.steps.len(), 2);
+
+    let mut playback = Simulation::with_plugins(FIRST_STEPS, replays);
+
+    // two or three physics steps per frame instead of one
+    playback
+        .world_mut()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            2.5 / 60.0,
+        )));
+    playback.world_mut().send_event(PlayReplayEvent { replay });
+
+    // the replay starts with the next update and is over once the player has control again
+    playback.step(1);
+    while playback.world().contains_resource::<ReplayPlayback>() {
+        assert!(
+            playback.frames() < MAX_PLAYBACK_FRAMES,
+            "replay is still playing after {} frames",
+            MAX_PLAYBACK_FRAMES
+        );
+        playback.step(1);
+    }
+
+    assert_eq!(playback.game_state(), GameState::Won);
+    assert!(playback.donut_summoned());
+    assert_eq!(
+        playback.world().resource::<MachineSteps>().0,
+        recorded_machine_steps
+    );
+}
@@ -0,0 +1,404 @@
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    window::{PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{GameState, RuneEffect, RuneEffectType, campaign::CampaignProgress, score::LevelScore};
+
+/*
+========================================================================================
+Save data
+
+Progress (completed levels and their best scores), discovered rune effects and settings survive a restart:
+- desktop: a RON file in the platform data dir
+- browser: localStorage
+The save is loaded once at startup and written whenever one of its parts changes
+Save files carry a version; old versions are migrated, corrupt files are set aside and the game starts fresh
+========================================================================================
+*/
+
+// bump when the format changes in a way serde defaults can't handle, and add a migration
+const SAVE_VERSION: u32 = 1;
+
+const SAVE_FILE_NAME: &str = "save.ron";
+// browser storage keys are this prefix plus the file name without extension, e.g. bevy-jam-6-save
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY_PREFIX: &str = "bevy-jam-6-";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        // note: loaded right away instead of in a startup system, as the level select screen is shown before those run
+        let (save_data, save_status) = load_save_data();
+
+        app.insert_resource(CampaignProgress {
+            completed_levels: save_data.completed_levels,
+        })
+        .insert_resource(DiscoveredRuneEffects {
+            rune_effect_types: save_data.discovered_rune_effects,
+        })
+        .insert_resource(save_data.settings)
+        .insert_resource(save_status)
+        .add_systems(
+            Update,
+            (
+                discover_rune_effects.run_if(in_state(GameState::MachineRunning)),
+                toggle_fullscreen,
+                apply_settings.run_if(resource_changed::<Settings>),
+            ),
+        )
+        .add_systems(
+            Last,
+            write_save_data.run_if(
+                resource_changed::<CampaignProgress>
+                    .or(resource_changed::<DiscoveredRuneEffects>)
+                    .or(resource_changed::<Settings>),
+            ),
+        );
+    }
+}
+
+/*
+Rune effects the player has seen in action at least once
+ */
+#[derive(Resource, Default)]
+pub struct DiscoveredRuneEffects {
+    pub rune_effect_types: HashSet<RuneEffectType>,
+}
+
+/*
+Player settings
+ */
+#[derive(Resource, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    // toggled with F11
+    pub fullscreen: bool,
+}
+
+/*
+Whether the save may be written
+ */
+#[derive(Resource, Default)]
+struct SaveStatus {
+    // the save is from a newer version of the game or could not be read -> never overwrite it
+    read_only: bool,
+}
+
+/*
+Everything that is saved; new fields need a serde default, so older saves of the same version still load
+saves from before versioning have no version field and are read as version 0
+ */
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SaveData {
+    version: u32,
+    // best score per level path
+    completed_levels: HashMap<String, LevelScore>,
+    discovered_rune_effects: HashSet<RuneEffectType>,
+    settings: Settings,
+}
+
+/*
+Only the version of a save; read before everything else, so a save of a newer version is recognized
+even if it can't be parsed (e.g. a new rune effect type)
+ */
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("no data directory available")]
+    NoDataDirectory,
+    #[cfg(target_arch = "wasm32")]
+    #[error("localStorage is not available")]
+    NoStorage,
+    #[error("could not read or write file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse save: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not serialize save: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+impl SaveData {
+    /*
+    Parses a save and brings it up to the current version
+     */
+    fn from_ron(save: &str) -> Result<Self, SaveError> {
+        // note: version 0 has the same layout, just no version field -> nothing to migrate yet
+        Ok(ron::from_str(save)?)
+    }
+}
+
+/*
+Loads the save, if there is one; a save that can't be used is replaced by a fresh one
+ */
+fn load_save_data() -> (SaveData, SaveStatus) {
+    let save = match read_save() {
+        Ok(Some(save)) => save,
+        Ok(None) => {
+            info!("No save found, starting fresh");
+            return (SaveData::default(), SaveStatus::default());
+        }
+        Err(error) => {
+            // the save may be fine, it just could not be read right now -> keep it
+            warn!("Could not read save, progress will not be saved: {}", error);
+            return (SaveData::default(), SaveStatus { read_only: true });
+        }
+    };
+
+    let save_version = match ron::from_str::<SaveHeader>(&save) {
+        Ok(save_header) => save_header.version,
+        Err(error) => {
+            warn!("Save is corrupt, starting fresh: {}", error);
+            set_aside_corrupt_save();
+            return (SaveData::default(), SaveStatus::default());
+        }
+    };
+
+    // don't clobber a save of a newer game version; it is read as well as possible (unknown fields are ignored)
+    if save_version > SAVE_VERSION {
+        warn!(
+            "Save is from a newer version of the game ({} > {}), progress will not be saved",
+            save_version, SAVE_VERSION
+        );
+
+        let save_data = SaveData::from_ron(&save).unwrap_or_else(|error| {
+            warn!("Could not read save of the newer version: {}", error);
+            SaveData::default()
+        });
+
+        return (save_data, SaveStatus { read_only: true });
+    }
+
+    let save_data = match SaveData::from_ron(&save) {
+        Ok(save_data) => save_data,
+        Err(error) => {
+            warn!("Save is corrupt, starting fresh: {}", error);
+            set_aside_corrupt_save();
+            return (SaveData::default(), SaveStatus::default());
+        }
+    };
+
+    info!(
+        "Save loaded: {} levels completed, {} rune effects discovered",
+        save_data.completed_levels.len(),
+        save_data.discovered_rune_effects.len()
+    );
+
+    (save_data, SaveStatus::default())
+}
+
+/*
+Writes the current progress, discovered rune effects and settings
+ */
+fn write_save_data(
+    // Globals
+    campaign_progress: Res<CampaignProgress>,
+    discovered_rune_effects: Res<DiscoveredRuneEffects>,
+    settings: Res<Settings>,
+    save_status: Res<SaveStatus>,
+) {
+    if save_status.read_only {
+        return;
+    }
+
+    let save_data = SaveData {
+        version: SAVE_VERSION,
+        completed_levels: campaign_progress.completed_levels.clone(),
+        discovered_rune_effects: discovered_rune_effects.rune_effect_types.clone(),
+        settings: settings.clone(),
+    };
+
+    let result = ron::ser::to_string_pretty(&save_data, ron::ser::PrettyConfig::default())
+        .map_err(SaveError::from)
+        .and_then(|save| write_save(&save));
+
+    match result {
+        Ok(()) => debug!("Save written"),
+        Err(error) => warn!("Could not write save: {}", error),
+    }
+}
+
+/*
+Remembers every rune effect that is applied to a machine part
+ */
+fn discover_rune_effects(
+    // Globals
+    mut discovered_rune_effects: ResMut<DiscoveredRuneEffects>,
+    // Queries
+    applied_rune_effects: Query<&RuneEffect, Added<RuneEffect>>,
+) {
+    for rune_effect in applied_rune_effects {
+        // note: only touch the resource for new discoveries, so the save is not written every time
+        if !discovered_rune_effects
+            .rune_effect_types
+            .contains(&rune_effect.rune_effect_type)
+        {
+            info!("Rune effect discovered: {:?}", rune_effect.rune_effect_type);
+            discovered_rune_effects
+                .rune_effect_types
+                .insert(rune_effect.rune_effect_type);
+        }
+    }
+}
+
+fn toggle_fullscreen(
+    // Globals
+    input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+) {
+    if input.just_pressed(KeyCode::F11) {
+        settings.fullscreen = !settings.fullscreen;
+    }
+}
+
+/*
+Applies the settings to the window
+ */
+fn apply_settings(
+    // Globals
+    settings: Res<Settings>,
+    // Queries
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = primary_window.single_mut() else {
+        return;
+    };
+
+    window.mode = if settings.fullscreen {
+        WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+    } else {
+        WindowMode::Windowed
+    };
+}
+
+/*
+========================================================================================
+Storage
+========================================================================================
+*/
+
+fn read_save() -> Result<Option<String>, SaveError> {
+    read_data_file(SAVE_FILE_NAME)
+}
+
+fn write_save(save: &str) -> Result<(), SaveError> {
+    write_data_file(SAVE_FILE_NAME, save)
+}
+
+/*
+Files next to the save (e.g. replays) use the same storage
+ */
+#[cfg(not(target_arch = "wasm32"))]
+fn data_file_path(file_name: &str) -> Result<std::path::PathBuf, SaveError> {
+    // tests (e.g. of replays) never touch the player's files
+    if cfg!(test) {
+        return Ok(std::env::temp_dir()
+            .join("bevy-jam-6-tests")
+            .join(file_name));
+    }
+
+    let project_dirs =
+        directories::ProjectDirs::from("", "", "bevy-jam-6").ok_or(SaveError::NoDataDirectory)?;
+
+    Ok(project_dirs.data_dir().join(file_name))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_data_file(file_name: &str) -> Result<Option<String>, SaveError> {
+    match std::fs::read_to_string(data_file_path(file_name)?) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_data_file(file_name: &str, contents: &str) -> Result<(), SaveError> {
+    let file_path = data_file_path(file_name)?;
+
+    if let Some(data_dir) = file_path.parent() {
+        std::fs::create_dir_all(data_dir)?;
+    }
+
+    // write next to the file first, so a crash while writing never leaves a half written file behind
+    let temp_file_path = file_path.with_file_name(format!("{}.tmp", file_name));
+    std::fs::write(&temp_file_path, contents)?;
+    std::fs::rename(temp_file_path, file_path)?;
+
+    Ok(())
+}
+
+/*
+Keeps a corrupt save next to the new one, so it can still be looked at
+ */
+#[cfg(not(target_arch = "wasm32"))]
+fn set_aside_corrupt_save() {
+    let Ok(save_file_path) = data_file_path(SAVE_FILE_NAME) else {
+        return;
+    };
+
+    if let Err(error) = std::fs::rename(
+        &save_file_path,
+        save_file_path.with_extension("ron.corrupt"),
+    ) {
+        warn!("Could not set aside corrupt save: {}", error);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, SaveError> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or(SaveError::NoStorage)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage_key(file_name: &str) -> String {
+    format!(
+        "{}{}",
+        STORAGE_KEY_PREFIX,
+        file_name.trim_end_matches(".ron")
+    )
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_data_file(file_name: &str) -> Result<Option<String>, SaveError> {
+    Ok(local_storage()?
+        .get_item(&storage_key(file_name))
+        .ok()
+        .flatten())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_data_file(file_name: &str, contents: &str) -> Result<(), SaveError> {
+    local_storage()?
+        .set_item(&storage_key(file_name), contents)
+        .map_err(|_| SaveError::NoStorage)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn set_aside_corrupt_save() {
+    let Ok(local_storage) = local_storage() else {
+        return;
+    };
+
+    let save_storage_key = storage_key(SAVE_FILE_NAME);
+
+    if let Ok(Some(save)) = local_storage.get_item(&save_storage_key) {
+        let _ = local_storage.set_item(&format!("{}-corrupt", save_storage_key), &save);
+    }
+}
+
+#[cfg(test)]
+mod tests;
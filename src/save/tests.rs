@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+
+use bevy::ecs::system::RunSystemOnce;
+
+use super::*;
+
+// all tests use the same save file (in the temp dir, see data_file_path)
+static SAVE_FILE: Mutex<()> = Mutex::new(());
+
+/*
+A save of a future version: a new field and a rune effect type this version doesn't know
+ */
+const NEWER_SAVE: &str = r#"(
+    version: 2,
+    completed_levels: {},
+    discovered_rune_effects: [MoveUp, Warp],
+    settings: (fullscreen: true),
+    achievements: [FirstDonut],
+)"#;
+
+fn save_file_path() -> std::path::PathBuf {
+    data_file_path(SAVE_FILE_NAME).unwrap()
+}
+
+fn corrupt_save_file_path() -> std::path::PathBuf {
+    save_file_path().with_extension("ron.corrupt")
+}
+
+/*
+Puts the save file in place (or removes it) and removes what earlier tests have left behind
+ */
+fn prepare_save_file(save: Option<&str>) {
+    let _ = std::fs::remove_file(corrupt_save_file_path());
+
+    match save {
+        Some(save) => write_save(save).unwrap(),
+        None => {
+            let _ = std::fs::remove_file(save_file_path());
+        }
+    }
+}
+
+/*
+Runs the save system once with some progress, as if the campaign had just changed
+ */
+fn write_progress(read_only: bool) {
+    let mut world = World::new();
+
+    let mut completed_levels = HashMap::default();
+    completed_levels.insert(
+        "levels/01_first_steps.level.ron".to_string(),
+        LevelScore {
+            score: 900,
+            stars: 2,
+        },
+    );
+
+    world.insert_resource(CampaignProgress { completed_levels });
+    world.insert_resource(DiscoveredRuneEffects::default());
+    world.insert_resource(Settings::default());
+    world.insert_resource(SaveStatus { read_only });
+
+    world.run_system_once(write_save_data).unwrap();
+}
+
+#[test]
+fn save_header_reads_the_version_of_a_newer_save() {
+    let save_header: SaveHeader = ron::from_str(NEWER_SAVE).unwrap();
+    assert_eq!(save_header.version, 2);
+
+    // the unknown rune effect type makes the save itself unreadable for this version
+    assert!(SaveData::from_ron(NEWER_SAVE).is_err());
+}
+
+#[test]
+fn newer_save_is_loaded_read_only_and_kept() {
+    let _save_file = SAVE_FILE.lock().unwrap();
+    prepare_save_file(Some(NEWER_SAVE));
+
+    let (_, save_status) = load_save_data();
+    assert!(save_status.read_only);
+
+    write_progress(save_status.read_only);
+
+    assert_eq!(
+        std::fs::read_to_string(save_file_path()).unwrap(),
+        NEWER_SAVE
+    );
+    assert!(!corrupt_save_file_path().exists());
+}
+
+#[test]
+fn progress_is_written_unless_read_only() {
+    let _save_file = SAVE_FILE.lock().unwrap();
+    prepare_save_file(None);
+
+    write_progress(true);
+    assert!(!save_file_path().exists());
+
+    write_progress(false);
+
+    let (save_data, save_status) = load_save_data();
+    assert!(!save_status.read_only);
+    assert_eq!(save_data.version, SAVE_VERSION);
+    assert_eq!(
+        save_data.completed_levels["levels/01_first_steps.level.ron"].score,
+        900
+    );
+}
+
+#[test]
+fn corrupt_save_is_set_aside() {
+    let _save_file = SAVE_FILE.lock().unwrap();
+    let corrupt_save = "(version: 1, completed_levels: {";
+    prepare_save_file(Some(corrupt_save));
+
+    let (save_data, save_status) = load_save_data();
+    assert!(!save_status.read_only);
+    assert!(save_data.completed_levels.is_empty());
+
+    assert!(!save_file_path().exists());
+    assert_eq!(
+        std::fs::read_to_string(corrupt_save_file_path()).unwrap(),
+        corrupt_save
+    );
+}
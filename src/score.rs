@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{FINAL_BALL_GOLD, GameState};
+
+/*
+========================================================================================
+Scoring
+
+Every won level is scored:
+- a base score for solving the level
+- minus a penalty for every attempt after the first, every rune used and every second spent
+- plus a bonus for every test ball that was not needed
+The score is turned into 1-3 stars, using thresholds defined per level
+-> the win screen and the level select screen show the stars, the score itself is logged
+========================================================================================
+*/
+
+const BASE_SCORE: u32 = 1000;
+// for every ball launched after the first one
+const ATTEMPT_PENALTY: u32 = 100;
+// for every rune placed in a rune slot when the level is won
+const RUNE_PENALTY: u32 = 50;
+// for every full second between starting the level and winning it (building and running)
+const SECOND_PENALTY: u32 = 2;
+// for every test ball that is left when the final ball succeeds
+const UNUSED_TEST_BALL_BONUS: u32 = 100;
+
+const MAX_STARS: u8 = 3;
+
+const STAR_IMAGE: &str = "UI Pack/PNG/Grey/Double/star_outline.png";
+const MISSING_STAR_GREY: Color = Color::LinearRgba(LinearRgba::rgb(0.3, 0.3, 0.3));
+
+pub struct ScoringPlugin;
+
+impl Plugin for ScoringPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelScoring>()
+            .init_resource::<LevelStats>()
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Building,
+                    entered: GameState::MachineRunning,
+                },
+                count_attempt,
+            )
+            .add_systems(OnEnter(GameState::Won), show_win_screen)
+            .add_systems(OnExit(GameState::Won), hide_win_screen)
+            // note: counts physics steps, so a replay of a level is scored the same
+            .add_systems(
+                FixedUpdate,
+                tick_level_clock
+                    .run_if(in_state(GameState::Building).or(in_state(GameState::MachineRunning))),
+            );
+    }
+}
+
+/*
+Star thresholds of the current level; set when a level is spawned
+one star is given for solving the level at all
+ */
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LevelScoring {
+    pub two_stars: u32,
+    pub three_stars: u32,
+}
+
+impl Default for LevelScoring {
+    fn default() -> Self {
+        LevelScoring {
+            two_stars: 700,
+            three_stars: 1000,
+        }
+    }
+}
+
+/*
+What the player has done in the current level so far; reset when a level is spawned
+ */
+#[derive(Resource, Default)]
+pub struct LevelStats {
+    // balls launched
+    attempts: u32,
+    // time spent building and running, pauses excluded
+    seconds: f32,
+}
+
+/*
+Score of a won level; the best one per level is kept by the campaign
+ */
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Default, Debug)]
+pub struct LevelScore {
+    pub score: u32,
+    pub stars: u8,
+}
+
+impl LevelScore {
+    pub fn new(
+        level_scoring: &LevelScoring,
+        level_stats: &LevelStats,
+        runes_used: u32,
+        unused_test_balls: u32,
+    ) -> Self {
+        let penalty = level_stats.attempts.saturating_sub(1) * ATTEMPT_PENALTY
+            + runes_used * RUNE_PENALTY
+            + level_stats.seconds as u32 * SECOND_PENALTY;
+
+        let score = BASE_SCORE.saturating_sub(penalty) + unused_test_balls * UNUSED_TEST_BALL_BONUS;
+
+        let stars = if score >= level_scoring.three_stars {
+            3
+        } else if score >= level_scoring.two_stars {
+            2
+        } else {
+            1
+        };
+
+        info!(
+            "Score: {} ({} attempts, {} runes, {:.0} seconds, {} unused test balls) -> {} stars",
+            score, level_stats.attempts, runes_used, level_stats.seconds, unused_test_balls, stars
+        );
+
+        LevelScore { score, stars }
+    }
+}
+
+/*
+Marks the root UI node of the win screen
+ */
+#[derive(Component)]
+struct WinScreen;
+
+/*
+Every launched ball counts as an attempt
+ */
+fn count_attempt(
+    // Globals
+    mut level_stats: ResMut<LevelStats>,
+) {
+    level_stats.attempts += 1;
+}
+
+/*
+Measures how long the player takes to solve the level
+ */
+fn tick_level_clock(
+    // Globals
+    time: Res<Time<Fixed>>,
+    mut level_stats: ResMut<LevelStats>,
+) {
+    level_stats.seconds += time.delta_secs();
+}
+
+/*
+Shows the stars of the won level while the donut is admired
+ */
+fn show_win_screen(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level_score: Option<Res<LevelScore>>,
+) {
+    let Some(level_score) = level_score else {
+        warn!("Level won, but it has not been scored");
+        return;
+    };
+
+    commands
+        .spawn((
+            WinScreen,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(32.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(16.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            spawn_stars(parent, &asset_server, level_score.stars, 96.0);
+        });
+}
+
+/*
+Removes the win screen once the next level starts
+ */
+fn hide_win_screen(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    win_screens: Query<Entity, With<WinScreen>>,
+) {
+    for win_screen in win_screens {
+        commands.entity(win_screen).despawn();
+    }
+}
+
+/*
+Spawns MAX_STARS star icons; the earned ones are gold, the missing ones grey
+ */
+pub fn spawn_stars(
+    parent: &mut ChildSpawnerCommands,
+    asset_server: &AssetServer,
+    stars: u8,
+    size: f32,
+) {
+    for star in 0..MAX_STARS {
+        let star_color = if star < stars {
+            FINAL_BALL_GOLD
+        } else {
+            MISSING_STAR_GREY
+        };
+
+        parent.spawn((
+            ImageNode::new(asset_server.load(STAR_IMAGE)).with_color(star_color),
+            Node {
+                width: Val::Px(size),
+                // the star image is not quite square
+                height: Val::Px(size * 45.0 / 48.0),
+                ..default()
+            },
+        ));
+    }
+}
@@ -0,0 +1,427 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    app::Plugins,
+    ecs::event::EventCursor,
+    image::{CompressedImageFormats, ImageLoader},
+    prelude::*,
+    scene::ScenePlugin,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+
+use crate::{
+    AddCollider, BallFiringThingy, BlueBall, FinalBall, GameState, GreyBall, MachineFailedEvent,
+    MachineFailureReason, MachinePlugin, Placed, RenderLayer, Rune, RuneSlot, SummonedDonut,
+    level::{CurrentLevel, LevelDefinition, LevelEntityId},
+    score::LevelStats,
+    stall::StallDetection,
+};
+
+/*
+========================================================================================
+Headless simulation
+
+Runs the machine without a window, rendering or player input:
+- every update advances time by exactly one physics step -> the same actions always lead to the same result
+- levels are loaded from assets/levels, just like in the game
+- runes are placed and balls are launched the same way the player does it, only without the mouse
+-> levels can be checked automatically
+========================================================================================
+*/
+
+// updates (and physics steps) per simulated second
+const SIMULATION_HZ: f64 = 60.0;
+
+// loading happens in the background; give up if the level is not there by then
+const MAX_LOADING_TIME: Duration = Duration::from_secs(30);
+
+// frames a dragged entity rests at its new position before it is released, so the collisions are up to date
+const SETTLE_FRAMES: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BallKind {
+    Test,
+    Final,
+}
+
+/*
+How a run of the machine ended
+ */
+#[derive(Clone, Copy, Debug)]
+pub enum MachineOutcome {
+    // the final ball summoned the donut
+    Won,
+    // a test ball summoned the donut, the level goes on
+    TestRunSucceeded,
+    Failed(MachineFailureReason),
+}
+
+pub struct Simulation {
+    app: App,
+    machine_failed_event_cursor: EventCursor<MachineFailedEvent>,
+    // frames since the level has been loaded
+    frames: u32,
+}
+
+/*
+App with everything the machine needs, but nothing that needs a window
+ */
+pub fn headless_app<M>(plugins: impl Plugins<M>) -> App {
+    let mut app = App::new();
+
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        StatesPlugin,
+        TransformPlugin,
+        // avian looks for colliders in scenes
+        ScenePlugin,
+    ))
+    // images are only needed for their size (colliders), nothing is rendered
+    .init_asset::<Image>()
+    .register_asset_loader(ImageLoader::new(CompressedImageFormats::NONE))
+    // one fixed step per update, regardless of how long an update really takes
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / SIMULATION_HZ,
+    )))
+    .insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+    .add_plugins(MachinePlugin)
+    .add_plugins(plugins);
+
+    // normally done by App::run, which is not used here
+    app.finish();
+    app.cleanup();
+
+    app
+}
+
+impl Simulation {
+    /*
+    Loads the level (path relative to assets) and waits until it can be built on
+     */
+    pub fn new(level_path: &str) -> Self {
+        Self::with_plugins(level_path, ())
+    }
+
+    /*
+    Like new, with more of the game than just the machine (e.g. replays)
+     */
+    pub fn with_plugins<M>(level_path: &str, plugins: impl Plugins<M>) -> Self {
+        let app = headless_app(plugins);
+        let handle = app.world().resource::<AssetServer>().load(level_path);
+
+        Self::start_level(app, handle, level_path)
+    }
+
+    /*
+    Runs a level that has no file, e.g. one written in a test to check a single feature of the machine
+     */
+    pub fn from_level(level: LevelDefinition) -> Self {
+        let mut app = headless_app(());
+        let handle = app
+            .world_mut()
+            .resource_mut::<Assets<LevelDefinition>>()
+            .add(level);
+
+        Self::start_level(app, handle, "(level without file)")
+    }
+
+    /*
+    Starts the level and waits until it can be built on
+     */
+    fn start_level(mut app: App, handle: Handle<LevelDefinition>, level_name: &str) -> Self {
+        app.insert_resource(CurrentLevel {
+            campaign_index: None,
+            handle,
+            spawned: false,
+        });
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Building);
+
+        let loading_started = Instant::now();
+
+        // the level is ready once it has been spawned and every entity has its collider
+        loop {
+            app.update();
+
+            let spawned = app.world().resource::<CurrentLevel>().spawned;
+            let colliders_pending = app
+                .world_mut()
+                .query_filtered::<(), With<AddCollider>>()
+                .iter(app.world())
+                .next()
+                .is_some();
+
+            if spawned && !colliders_pending {
+                break;
+            }
+
+            assert!(
+                loading_started.elapsed() < MAX_LOADING_TIME,
+                "level '{}' could not be loaded",
+                level_name
+            );
+        }
+
+        // time spent loading does not count for the score
+        app.insert_resource(LevelStats::default());
+
+        let mut simulation = Simulation {
+            app,
+            machine_failed_event_cursor: EventCursor::default(),
+            frames: 0,
+        };
+
+        // let the physics pick up all colliders
+        simulation.step(SETTLE_FRAMES);
+
+        simulation
+    }
+
+    pub fn step(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.app.update();
+            self.frames += 1;
+        }
+    }
+
+    /*
+    Frames since the level has been loaded
+     */
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn world(&self) -> &World {
+        self.app.world()
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    pub fn game_state(&self) -> GameState {
+        *self.app.world().resource::<State<GameState>>().get()
+    }
+
+    /*
+    Finds a level entity by the id it has in the level file
+     */
+    pub fn entity(&mut self, id: &str) -> Entity {
+        self.app
+            .world_mut()
+            .query::<(Entity, &LevelEntityId)>()
+            .iter(self.app.world())
+            .find(|(_, level_entity_id)| level_entity_id.0 == id)
+            .map(|(entity, _)| entity)
+            .unwrap_or_else(|| panic!("level has no entity with id '{}'", id))
+    }
+
+    /*
+    All runes of the level, in the same order for every simulation of the level
+     */
+    pub fn runes(&mut self) -> Vec<Entity> {
+        self.sorted_entities::<Rune>()
+    }
+
+    /*
+    All rune slots of the level, in the same order for every simulation of the level
+     */
+    pub fn rune_slots(&mut self) -> Vec<Entity> {
+        self.sorted_entities::<RuneSlot>()
+    }
+
+    /*
+    The id the entity has in the level file, or the entity itself if it has none
+     */
+    pub fn name(&self, entity: Entity) -> String {
+        match self.app.world().get::<LevelEntityId>(entity) {
+            Some(level_entity_id) => level_entity_id.0.clone(),
+            None => entity.to_string(),
+        }
+    }
+
+    /*
+    Drags the rune onto the rune slot and drops it there; returns whether the rune ended up in the slot
+     */
+    pub fn place_rune(&mut self, rune_id: &str, rune_slot_id: &str) -> bool {
+        let rune = self.entity(rune_id);
+        let rune_slot = self.entity(rune_slot_id);
+
+        self.place_rune_entity(rune, rune_slot)
+    }
+
+    pub fn place_rune_entity(&mut self, rune: Entity, rune_slot: Entity) -> bool {
+        let rune_slot_position = self
+            .app
+            .world()
+            .get::<GlobalTransform>(rune_slot)
+            .expect("rune slot has no transform")
+            .translation()
+            .truncate();
+
+        self.drop_at(rune, rune_slot_position);
+
+        self.app
+            .world()
+            .get::<ChildOf>(rune)
+            .is_some_and(|child_of| child_of.parent() == rune_slot)
+    }
+
+    /*
+    Drags the next unused ball of the given kind onto the (first) ball firing thingy; returns whether the machine has started
+     */
+    pub fn launch_ball(&mut self, ball_kind: BallKind) -> bool {
+        let ball = self
+            .app
+            .world_mut()
+            .query_filtered::<(Entity, Has<FinalBall>), (With<GreyBall>, Without<BlueBall>)>()
+            .iter(self.app.world())
+            .filter(|(_, is_final)| *is_final == (ball_kind == BallKind::Final))
+            .map(|(ball, _)| ball)
+            .min();
+
+        let Some(ball) = ball else {
+            return false;
+        };
+
+        let Some(ball_firing_thingy_position) = self
+            .app
+            .world_mut()
+            .query_filtered::<&Transform, With<BallFiringThingy>>()
+            .iter(self.app.world())
+            .map(|transform| transform.translation.truncate())
+            .next()
+        else {
+            return false;
+        };
+
+        self.drop_at(ball, ball_firing_thingy_position);
+
+        // the state changes with the next update
+        self.step(1);
+
+        self.game_state() == GameState::MachineRunning
+    }
+
+    /*
+    Runs the machine until the run is over (won, failed or a successful test run)
+     */
+    pub fn run_machine(&mut self) -> MachineOutcome {
+        // the stall detection fails the machine at the latest after the timeout; one more second as safety net
+        let timeout_seconds = self
+            .app
+            .world()
+            .resource::<StallDetection>()
+            .timeout_seconds;
+        let max_frames = ((timeout_seconds as f64 + 1.0) * SIMULATION_HZ) as u32;
+
+        let mut machine_failure_reason = None;
+
+        for _ in 0..max_frames {
+            self.step(1);
+
+            let machine_failed_events = self.app.world().resource::<Events<MachineFailedEvent>>();
+
+            for machine_failed_event in self.machine_failed_event_cursor.read(machine_failed_events)
+            {
+                // the first failure is the reason, everything after is a consequence
+                machine_failure_reason.get_or_insert(machine_failed_event.reason);
+            }
+
+            let machine_outcome = match self.game_state() {
+                GameState::MachineRunning => continue,
+                GameState::Won => MachineOutcome::Won,
+                // failures in the same physics step as the donut don't count
+                _ if self.donut_summoned() => MachineOutcome::TestRunSucceeded,
+                _ => match machine_failure_reason {
+                    Some(machine_failure_reason) => MachineOutcome::Failed(machine_failure_reason),
+                    None => MachineOutcome::TestRunSucceeded,
+                },
+            };
+
+            // the machine parts are put back with the next update(s)
+            self.step(SETTLE_FRAMES);
+
+            return machine_outcome;
+        }
+
+        panic!("machine is still running after {} frames", max_frames);
+    }
+
+    /*
+    Whether the donut has been summoned (and is still shown)
+     */
+    pub fn donut_summoned(&mut self) -> bool {
+        self.app
+            .world_mut()
+            .query_filtered::<(), With<SummonedDonut>>()
+            .iter(self.app.world())
+            .next()
+            .is_some()
+    }
+
+    /*
+    Spawning a level is deterministic, so sorting gives the same order every time
+     */
+    fn sorted_entities<T: Component>(&mut self) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = self
+            .app
+            .world_mut()
+            .query_filtered::<Entity, With<T>>()
+            .iter(self.app.world())
+            .collect();
+        entities.sort();
+
+        entities
+    }
+
+    /*
+    Moves the entity to the position, lets it rest there and releases it, just like the player would do with the mouse
+     */
+    fn drop_at(&mut self, entity: Entity, position: Vec2) {
+        // picked up first, so a placed rune leaves its rune slot (see handle_event_pick)
+        if let Some(parent) = self.app.world().get::<ChildOf>(entity).map(ChildOf::parent) {
+            self.app
+                .world_mut()
+                .entity_mut(parent)
+                .remove_children(&[entity]);
+
+            let render_layer = self
+                .app
+                .world()
+                .get::<RenderLayer>(entity)
+                .map(|render_layer| render_layer.render_layer);
+
+            let mut transform = self
+                .app
+                .world_mut()
+                .get_mut::<Transform>(entity)
+                .expect("dropped entity has no transform");
+            transform.scale = Vec3::ONE;
+
+            if let Some(render_layer) = render_layer {
+                transform.translation.z = render_layer;
+            }
+        }
+
+        let mut transform = self
+            .app
+            .world_mut()
+            .get_mut::<Transform>(entity)
+            .expect("dropped entity has no transform");
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+
+        self.step(SETTLE_FRAMES);
+
+        self.app.world_mut().entity_mut(entity).insert(Placed);
+
+        self.step(1);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,440 @@
+use super::*;
+use crate::{RuneEffect, timing::MetronomeNode};
+
+const FIRST_STEPS: &str = "levels/01_first_steps.level.ron";
+const MVP: &str = "levels/02_mvp.level.ron";
+
+/*
+The card of the first level pushed into a trigger star, which feeds an And gate in front of the donut circle
+card_star_follow_up decides whether the star also starts the delay, the second input of the gate
+ */
+fn delay_and_gate_level(card_star_follow_up: &str) -> LevelDefinition {
+    let level_ron = format!(
+        r#"(
+            entities: [
+                (
+                    id: Some("move_up_rune"),
+                    kind: Rune(effect: (rune_effect_type: MoveUp, rune_effect_move_speed: Some((0.0, 200.0)))),
+                    position: (-530.0, -290.0),
+                    sprite: Some((image: "runes/PNG/Grey/Slab/runeGrey_slab_001.png")),
+                    collider: Some((collider_scale: 1.0, collider_type: Rectangle)),
+                ),
+                (
+                    kind: FinalBall,
+                    position: (550.0, -296.0),
+                    sprite: Some((image: "Puzzle Assets/PNG/Double/ballGrey.png")),
+                    collider: Some((collider_scale: 1.0, collider_type: Circle)),
+                ),
+                (
+                    kind: BallFiringThingy(
+                        firing_speed: 200.0,
+                        arrow_image: "UI Pack/PNG/Grey/Double/arrow_decorative_w.png",
+                    ),
+                    position: (580.0, -100.0),
+                    sprite: Some((image: "UI Pack/PNG/Blue/Double/check_round_round_circle.png")),
+                    collider: Some((collider_scale: 0.4, collider_type: Circle)),
+                ),
+                (
+                    id: Some("donut_circle"),
+                    kind: DonutCircle,
+                    position: (0.0, -280.0),
+                    sprite: Some((image: "Particle Pack/PNG (Transparent)/magic_02.png", custom_size: Some((200.0, 200.0)))),
+                ),
+                (
+                    id: Some("ace_of_hearts"),
+                    kind: Card(machine_part: true),
+                    position: (350.0, -100.0),
+                    sprite: Some((image: "Boardgame Pack/PNG/Cards/cardHeartsA.png")),
+                    collider: Some((collider_scale: 1.0, collider_type: Rectangle)),
+                ),
+                (
+                    id: Some("ace_of_hearts_rune_slot"),
+                    kind: RuneSlot(attached_to: "ace_of_hearts"),
+                    position: (0.0, 0.0),
+                    sprite: Some((image: "runes/PNG/Black/Slab/runeBlack_slab_036.png")),
+                    collider: Some((collider_scale: 0.5, collider_type: Rectangle)),
+                ),
+                (
+                    id: Some("card_star"),
+                    kind: StarTrigger(follow_up: [{card_star_follow_up}]),
+                    position: (350.0, 320.0),
+                    sprite: Some((image: "UI Pack/PNG/Grey/Double/star_outline.png", custom_size: Some((60.0, 60.0)))),
+                    collider: Some((collider_scale: 1.0, collider_type: Rectangle)),
+                ),
+                (
+                    id: Some("delay"),
+                    kind: Delay(seconds: 0.5, follow_up: ["and_gate"]),
+                    position: (100.0, 320.0),
+                ),
+                (
+                    id: Some("and_gate"),
+                    kind: Gate(gate: And, follow_up: ["donut_circle"]),
+                    position: (0.0, 320.0),
+                ),
+            ],
+        )"#
+    );
+
+    ron::from_str(&level_ron).expect("test level is invalid")
+}
+
+/*
+The card of the first level with a rune in its slot, in front of a ball firing thingy and nothing else
+ */
+fn rune_effect_level(rune_effect: &str) -> LevelDefinition {
+    let level_ron = format!(
+        r#"(
+            entities: [
+                (
+                    id: Some("effect_rune"),
+                    kind: Rune(effect: {rune_effect}),
+                    position: (-530.0, -290.0),
+                    sprite: Some((image: "runes/PNG/Grey/Slab/runeGrey_slab_001.png")),
+                    collider: Some((collider_scale: 1.0, collider_type: Rectangle)),
+                ),
+                (
+                    kind: FinalBall,
+                    position: (550.0, -296.0),
+                    sprite: Some((image: "Puzzle Assets/PNG/Double/ballGrey.png")),
+                    collider: Some((collider_scale: 1.0, collider_type: Circle)),
+                ),
+                (
+                    kind: BallFiringThingy(
+                        firing_speed: 200.0,
+                        arrow_image: "UI Pack/PNG/Grey/Double/arrow_decorative_w.png",
+                    ),
+                    position: (580.0, -100.0),
+                    sprite: Some((image: "UI Pack/PNG/Blue/Double/check_round_round_circle.png")),
+                    collider: Some((collider_scale: 0.4, collider_type: Circle)),
+                ),
+                (
+                    kind: DonutCircle,
+                    position: (0.0, -280.0),
+                    sprite: Some((image: "Particle Pack/PNG (Transparent)/magic_02.png", custom_size: Some((200.0, 200.0)))),
+                ),
+                (
+                    id: Some("ace_of_hearts"),
+                    kind: Card(machine_part: true),
+                    position: (350.0, -100.0),
+                    sprite: Some((image: "Boardgame Pack/PNG/Cards/cardHeartsA.png")),
+                    collider: Some((collider_scale: 1.0, collider_type: Rectangle)),
+                ),
+                (
+                    id: Some("ace_of_hearts_rune_slot"),
+                    kind: RuneSlot(attached_to: "ace_of_hearts"),
+                    position: (0.0, 0.0),
+                    sprite: Some((image: "runes/PNG/Black/Slab/runeBlack_slab_036.png")),
+                    collider: Some((collider_scale: 0.5, collider_type: Rectangle)),
+                ),
+            ],
+        )"#
+    );
+
+    ron::from_str(&level_ron).expect("test level is invalid")
+}
+
+/*
+Launches the ball into the card and runs the machine until the effect of the rune is over
+returns the card's transform at that moment and the frames the effect took
+ */
+fn apply_rune_effect(rune_effect: &str) -> (Transform, u32) {
+    let mut simulation = Simulation::from_level(rune_effect_level(rune_effect));
+
+    assert!(simulation.place_rune("effect_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    let card = simulation.entity("ace_of_hearts");
+    let mut effect_frames = 0;
+
+    loop {
+        simulation.step(1);
+
+        assert_eq!(
+            simulation.game_state(),
+            GameState::MachineRunning,
+            "machine stopped before the rune effect was over"
+        );
+
+        let world = simulation.world();
+        let transform = *world.get::<Transform>(card).unwrap();
+
+        let Some(rune_effect) = world.get::<RuneEffect>(card) else {
+            continue;
+        };
+
+        effect_frames += 1;
+
+        if !rune_effect.is_running(&transform) {
+            return (transform, effect_frames);
+        }
+    }
+}
+
+#[test]
+fn first_steps_is_won_with_the_move_up_rune() {
+    let mut simulation = Simulation::new(FIRST_STEPS);
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    assert!(matches!(simulation.run_machine(), MachineOutcome::Won));
+    assert!(simulation.donut_summoned());
+    assert_eq!(simulation.game_state(), GameState::Won);
+}
+
+#[test]
+fn first_steps_fails_at_the_dead_end_star_with_the_move_right_rune() {
+    let mut simulation = Simulation::new(FIRST_STEPS);
+
+    assert!(simulation.place_rune("move_right_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Test));
+
+    let card_stop_star = simulation.entity("card_stop_star");
+
+    match simulation.run_machine() {
+        MachineOutcome::Failed(MachineFailureReason::DeadEndTriggerStar {
+            trigger_star, ..
+        }) => {
+            assert_eq!(trigger_star, card_stop_star);
+        }
+        machine_outcome => panic!("unexpected outcome: {:?}", machine_outcome),
+    }
+
+    // a failed test run costs nothing but the ball
+    assert_eq!(simulation.game_state(), GameState::Building);
+    assert!(!simulation.donut_summoned());
+}
+
+#[test]
+fn first_steps_fails_without_runes() {
+    let mut simulation = Simulation::new(FIRST_STEPS);
+
+    assert!(simulation.launch_ball(BallKind::Test));
+
+    assert!(matches!(
+        simulation.run_machine(),
+        MachineOutcome::Failed(_)
+    ));
+    assert_eq!(simulation.game_state(), GameState::Building);
+}
+
+#[test]
+fn failing_with_the_final_ball_loses_the_level() {
+    let mut simulation = Simulation::new(FIRST_STEPS);
+
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    assert!(matches!(
+        simulation.run_machine(),
+        MachineOutcome::Failed(_)
+    ));
+    assert_eq!(simulation.game_state(), GameState::Lost);
+}
+
+#[test]
+fn test_run_summons_the_donut_without_winning() {
+    let mut simulation = Simulation::new(FIRST_STEPS);
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Test));
+
+    assert!(matches!(
+        simulation.run_machine(),
+        MachineOutcome::TestRunSucceeded
+    ));
+    assert!(simulation.donut_summoned());
+    assert_eq!(simulation.game_state(), GameState::Building);
+
+    // runes are put back after every run, the machine has to be built again
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    assert!(matches!(simulation.run_machine(), MachineOutcome::Won));
+}
+
+#[test]
+fn mvp_is_won_with_both_runes() {
+    let mut simulation = Simulation::new(MVP);
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.place_rune("move_right_rune", "letter_d_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    assert!(matches!(simulation.run_machine(), MachineOutcome::Won));
+}
+
+#[test]
+fn mvp_fails_at_the_empty_letter_rune_slot() {
+    let mut simulation = Simulation::new(MVP);
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Test));
+
+    let letter_d_rune_slot = simulation.entity("letter_d_rune_slot");
+
+    match simulation.run_machine() {
+        MachineOutcome::Failed(MachineFailureReason::EmptyRuneSlot { rune_slot, .. }) => {
+            assert_eq!(rune_slot, letter_d_rune_slot);
+        }
+        machine_outcome => panic!("unexpected outcome: {:?}", machine_outcome),
+    }
+}
+
+#[test]
+fn placing_a_rune_in_a_filled_slot_replaces_it() {
+    let mut simulation = Simulation::new(MVP);
+
+    assert!(simulation.place_rune("move_right_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+
+    let move_right_rune = simulation.entity("move_right_rune");
+    assert!(simulation.world().get::<ChildOf>(move_right_rune).is_none());
+}
+
+#[test]
+fn placed_rune_can_be_moved_to_another_slot() {
+    let mut simulation = Simulation::new(MVP);
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.place_rune("move_up_rune", "letter_d_rune_slot"));
+
+    let ace_of_hearts_rune_slot = simulation.entity("ace_of_hearts_rune_slot");
+    let ace_of_hearts_rune_slot_children = simulation
+        .world()
+        .get::<Children>(ace_of_hearts_rune_slot)
+        .map_or(0, |children| children.len());
+    assert_eq!(ace_of_hearts_rune_slot_children, 0);
+}
+
+#[test]
+fn same_actions_lead_to_the_same_run() {
+    let run = || {
+        let mut simulation = Simulation::new(MVP);
+
+        assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+        assert!(simulation.place_rune("move_right_rune", "letter_d_rune_slot"));
+        assert!(simulation.launch_ball(BallKind::Final));
+        assert!(matches!(simulation.run_machine(), MachineOutcome::Won));
+
+        let letter_d = simulation.entity("letter_d");
+        let letter_d_position = simulation
+            .world()
+            .get::<Transform>(letter_d)
+            .unwrap()
+            .translation;
+
+        (simulation.frames(), letter_d_position)
+    };
+
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn and_gate_waits_for_its_delay_input() {
+    let mut simulation = Simulation::from_level(delay_and_gate_level(r#""and_gate", "delay""#));
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    assert!(matches!(simulation.run_machine(), MachineOutcome::Won));
+}
+
+#[test]
+fn and_gate_does_not_fire_without_its_delay_input() {
+    // the delay is never started, so the gate only gets one of its two inputs
+    let mut simulation = Simulation::from_level(delay_and_gate_level(r#""and_gate""#));
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    assert!(matches!(
+        simulation.run_machine(),
+        MachineOutcome::Failed(_)
+    ));
+    assert!(!simulation.donut_summoned());
+}
+
+#[test]
+fn rotate_turns_in_the_direction_of_its_angle() {
+    // a positive speed, the negative angle still turns the card clockwise
+    let (transform, _) = apply_rune_effect(
+        "(rune_effect_type: Rotate, rune_effect_rotation_speed: Some(90.0), rune_effect_rotation_angle: Some(-90.0))",
+    );
+
+    let (_, _, angle) = transform.rotation.to_euler(EulerRot::XYZ);
+    assert!(
+        (angle.to_degrees() + 90.0).abs() < 0.01,
+        "card rotated to {} degrees",
+        angle.to_degrees()
+    );
+}
+
+#[test]
+fn scale_moves_towards_its_target_at_its_speed() {
+    // a positive speed, the smaller target still shrinks the card, over a second instead of at once
+    let (transform, effect_frames) = apply_rune_effect(
+        "(rune_effect_type: Scale, rune_effect_scale_speed: Some(0.5), rune_effect_scale_target: Some(0.5))",
+    );
+
+    assert_eq!(transform.scale.x, 0.5);
+    assert!(effect_frames >= 59, "scaling took {} frames", effect_frames);
+}
+
+#[test]
+fn metronome_stops_waiting_with_its_last_tick() {
+    // ticks twice, half a second apart, from the start of the machine
+    let mut level =
+        rune_effect_level("(rune_effect_type: MoveUp, rune_effect_move_speed: Some((0.0, 200.0)))");
+    level.entities.push(
+        ron::from_str(
+            r#"(
+                id: Some("metronome"),
+                kind: Metronome(interval_seconds: 0.5, repetitions: Some(2), autostart: true, follow_up: []),
+                position: (0.0, 320.0),
+            )"#,
+        )
+        .expect("test metronome is invalid"),
+    );
+
+    let mut simulation = Simulation::from_level(level);
+    let metronome = simulation.entity("metronome");
+
+    assert!(simulation.place_rune("effect_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    let is_waiting = |simulation: &Simulation| {
+        simulation
+            .world()
+            .get::<MetronomeNode>(metronome)
+            .unwrap()
+            .is_waiting()
+    };
+
+    let mut waiting_frames = 0;
+    while is_waiting(&simulation) {
+        assert_eq!(simulation.game_state(), GameState::MachineRunning);
+
+        simulation.step(1);
+        waiting_frames += 1;
+    }
+
+    // the second tick is one second into the run, not another interval later
+    assert!(
+        (59..=61).contains(&waiting_frames),
+        "metronome was waiting for {} frames",
+        waiting_frames
+    );
+}
+
+#[test]
+fn donut_summoned_in_the_same_step_as_a_failure_wins() {
+    // the card star triggers the card itself (nothing to trigger -> failure) and the donut circle at once
+    let mut simulation =
+        Simulation::from_level(delay_and_gate_level(r#""ace_of_hearts", "donut_circle""#));
+
+    assert!(simulation.place_rune("move_up_rune", "ace_of_hearts_rune_slot"));
+    assert!(simulation.launch_ball(BallKind::Final));
+
+    assert!(matches!(simulation.run_machine(), MachineOutcome::Won));
+    assert_eq!(simulation.game_state(), GameState::Won);
+}
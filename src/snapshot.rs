@@ -0,0 +1,184 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::{GameState, MachinePart, MachineResetEvent, RuneEffect, SidewaysGravity};
+
+/*
+========================================================================================
+Machine snapshots
+
+When a ball is launched (Building -> MachineRunning), the state of every machine part is stored
+If the machine fails (or a test run is over), every part is put back exactly how it was before the run
+-> works for any number and kind of machine parts, no start positions need to be known
+========================================================================================
+*/
+
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MachineSnapshot>()
+            // note: not OnEnter(MachineRunning), that would also run when resuming from pause
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Building,
+                    entered: GameState::MachineRunning,
+                },
+                capture_machine_snapshot,
+            )
+            .add_systems(FixedUpdate, restore_machine_snapshot);
+    }
+}
+
+/*
+Pre-run state of all machine parts
+ */
+#[derive(Resource, Default)]
+pub struct MachineSnapshot {
+    parts: Vec<MachinePartSnapshot>,
+}
+
+struct MachinePartSnapshot {
+    entity: Entity,
+    transform: Transform,
+    sprite_color: Option<Color>,
+    rune_effect: Option<RuneEffect>,
+    parent: Option<Entity>,
+    // gravity runes turn parts into dynamic bodies
+    rigid_body: Option<RigidBody>,
+    gravity_scale: Option<GravityScale>,
+    sideways_gravity: Option<SidewaysGravity>,
+}
+
+/*
+Stores the state of all machine parts when the machine is started
+ */
+fn capture_machine_snapshot(
+    // Globals
+    mut machine_snapshot: ResMut<MachineSnapshot>,
+    // Queries
+    machine_parts: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&Sprite>,
+            Option<&RuneEffect>,
+            Option<&ChildOf>,
+            Option<&RigidBody>,
+            Option<&GravityScale>,
+            Option<&SidewaysGravity>,
+        ),
+        With<MachinePart>,
+    >,
+) {
+    machine_snapshot.parts = machine_parts
+        .iter()
+        .map(
+            |(
+                entity,
+                transform,
+                sprite,
+                rune_effect,
+                child_of,
+                rigid_body,
+                gravity_scale,
+                sideways_gravity,
+            )| MachinePartSnapshot {
+                entity,
+                transform: *transform,
+                sprite_color: sprite.map(|sprite| sprite.color),
+                rune_effect: rune_effect.copied(),
+                parent: child_of.map(|child_of| child_of.parent()),
+                rigid_body: rigid_body.copied(),
+                gravity_scale: gravity_scale.copied(),
+                sideways_gravity: sideways_gravity.copied(),
+            },
+        )
+        .collect();
+
+    debug!(
+        "Machine snapshot captured for {} parts",
+        machine_snapshot.parts.len()
+    );
+}
+
+/*
+Puts all machine parts back into their pre-run state when the machine is reset
+ */
+pub fn restore_machine_snapshot(
+    // Globals
+    mut commands: Commands,
+    mut machine_reset_event_reader: EventReader<MachineResetEvent>,
+    machine_snapshot: Res<MachineSnapshot>,
+    // Queries
+    mut machine_parts: Query<
+        (
+            &mut Transform,
+            Option<&mut Sprite>,
+            Option<&mut LinearVelocity>,
+            Option<&mut AngularVelocity>,
+        ),
+        With<MachinePart>,
+    >,
+) {
+    // multiple resets in the same frame still only need a single restore
+    if machine_reset_event_reader.read().last().is_none() {
+        return;
+    }
+
+    for part in &machine_snapshot.parts {
+        let Ok((mut transform, sprite, linear_velocity, angular_velocity)) =
+            machine_parts.get_mut(part.entity)
+        else {
+            warn!(
+                "Machine part {} no longer exists, cannot restore it",
+                part.entity
+            );
+            continue;
+        };
+
+        *transform = part.transform;
+
+        if let (Some(mut sprite), Some(sprite_color)) = (sprite, part.sprite_color) {
+            sprite.color = sprite_color;
+        }
+
+        // parts that were falling must not keep their momentum
+        if let Some(mut linear_velocity) = linear_velocity {
+            linear_velocity.0 = Vec2::ZERO;
+        }
+
+        if let Some(mut angular_velocity) = angular_velocity {
+            angular_velocity.0 = 0.0;
+        }
+
+        let mut part_commands = commands.entity(part.entity);
+
+        match part.rune_effect {
+            Some(rune_effect) => part_commands.insert(rune_effect),
+            None => part_commands.remove::<RuneEffect>(),
+        };
+
+        match part.parent {
+            Some(parent) => part_commands.insert(ChildOf(parent)),
+            None => part_commands.remove::<ChildOf>(),
+        };
+
+        match part.rigid_body {
+            Some(rigid_body) => part_commands.insert(rigid_body),
+            None => part_commands.remove::<RigidBody>(),
+        };
+
+        match part.gravity_scale {
+            Some(gravity_scale) => part_commands.insert(gravity_scale),
+            None => part_commands.remove::<GravityScale>(),
+        };
+
+        match part.sideways_gravity {
+            Some(sideways_gravity) => part_commands.insert(sideways_gravity),
+            None => part_commands.remove::<SidewaysGravity>(),
+        };
+
+        trace!("machine part restored to {:?}", transform.translation);
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+
+use crate::simulation::{BallKind, MachineOutcome, Simulation};
+
+/*
+========================================================================================
+Puzzle solver
+
+Tries every way the runes of a level can be put into its rune slots (each slot empty or holding one rune,
+each rune used at most once, including no runes at all) and runs the machine headless for each of them
+-> shows whether a level can be solved, with how few runes, and every unintended shortcut
+Ball firing thingies are left as they are in the level file
+
+Run it for all campaign levels, or for a single level (path relative to assets):
+cargo test solve_levels -- --ignored --nocapture
+SOLVER_LEVEL=levels/02_mvp.level.ron cargo test solve_levels -- --ignored --nocapture
+========================================================================================
+*/
+
+/*
+Which rune goes into which rune slot, as indices into Simulation::runes / Simulation::rune_slots
+ */
+type RuneAssignment = Vec<(usize, usize)>;
+
+/*
+A rune layout that wins the level, as (rune, rune slot) names
+ */
+#[derive(Debug)]
+pub struct Solution {
+    pub placements: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct SolverReport {
+    pub level_path: String,
+    pub runes: usize,
+    pub rune_slots: usize,
+    pub rune_assignments_tried: usize,
+    // sorted by the number of runes used
+    pub solutions: Vec<Solution>,
+}
+
+impl SolverReport {
+    /*
+    Fewest runes any solution needs; None if the level can't be solved
+     */
+    pub fn min_runes(&self) -> Option<usize> {
+        self.solutions
+            .iter()
+            .map(|solution| solution.placements.len())
+            .min()
+    }
+}
+
+impl fmt::Display for SolverReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} runes, {} rune slots, {} rune assignments tried",
+            self.level_path, self.runes, self.rune_slots, self.rune_assignments_tried
+        )?;
+
+        let Some(min_runes) = self.min_runes() else {
+            return writeln!(f, "  not solvable");
+        };
+
+        writeln!(
+            f,
+            "  {} solutions, at least {} runes needed",
+            self.solutions.len(),
+            min_runes
+        )?;
+
+        for solution in &self.solutions {
+            if solution.placements.is_empty() {
+                writeln!(f, "  - no runes at all")?;
+                continue;
+            }
+
+            let placements: Vec<String> = solution
+                .placements
+                .iter()
+                .map(|(rune, rune_slot)| format!("{} -> {}", rune, rune_slot))
+                .collect();
+            writeln!(f, "  - {}", placements.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/*
+Runs the machine for every rune assignment of the level (path relative to assets)
+ */
+pub fn solve(level_path: &str) -> SolverReport {
+    let mut simulation = Simulation::new(level_path);
+    let runes = simulation.runes().len();
+    let rune_slots = simulation.rune_slots().len();
+
+    let mut rune_assignments = Vec::new();
+    collect_rune_assignments(
+        runes,
+        rune_slots,
+        &mut Vec::new(),
+        &mut vec![false; runes],
+        &mut rune_assignments,
+    );
+    rune_assignments.sort_by_key(Vec::len);
+
+    let solutions = rune_assignments
+        .iter()
+        .filter_map(|rune_assignment| try_rune_assignment(level_path, rune_assignment))
+        .collect();
+
+    SolverReport {
+        level_path: level_path.to_string(),
+        runes,
+        rune_slots,
+        rune_assignments_tried: rune_assignments.len(),
+        solutions,
+    }
+}
+
+/*
+Every slot from `rune_slot` on is either left empty or gets one of the runes that are still unused
+ */
+fn collect_rune_assignments(
+    runes: usize,
+    rune_slots: usize,
+    rune_assignment: &mut RuneAssignment,
+    rune_used: &mut Vec<bool>,
+    rune_assignments: &mut Vec<RuneAssignment>,
+) {
+    let rune_slot = rune_assignment
+        .last()
+        .map_or(0, |(_, rune_slot)| rune_slot + 1);
+
+    // the slots after the last one with a rune stay empty
+    rune_assignments.push(rune_assignment.clone());
+
+    for next_rune_slot in rune_slot..rune_slots {
+        for rune in 0..runes {
+            if rune_used[rune] {
+                continue;
+            }
+
+            rune_used[rune] = true;
+            rune_assignment.push((rune, next_rune_slot));
+
+            collect_rune_assignments(
+                runes,
+                rune_slots,
+                rune_assignment,
+                rune_used,
+                rune_assignments,
+            );
+
+            rune_assignment.pop();
+            rune_used[rune] = false;
+        }
+    }
+}
+
+/*
+Builds the machine in a fresh simulation and launches a ball; returns the solution if the level is won
+ */
+fn try_rune_assignment(level_path: &str, rune_assignment: &RuneAssignment) -> Option<Solution> {
+    let mut simulation = Simulation::new(level_path);
+    let runes = simulation.runes();
+    let rune_slots = simulation.rune_slots();
+
+    let mut placements = Vec::new();
+
+    for &(rune, rune_slot) in rune_assignment {
+        let (rune, rune_slot) = (runes[rune], rune_slots[rune_slot]);
+
+        if !simulation.place_rune_entity(rune, rune_slot) {
+            // note: printed, as the headless app has no logging
+            eprintln!(
+                "{} could not be placed in {}",
+                simulation.name(rune),
+                simulation.name(rune_slot)
+            );
+            return None;
+        }
+
+        placements.push((simulation.name(rune), simulation.name(rune_slot)));
+    }
+
+    // the final ball decides the level; in levels without one, every test ball does
+    if !simulation.launch_ball(BallKind::Final) && !simulation.launch_ball(BallKind::Test) {
+        eprintln!("{}: no ball could be launched", level_path);
+        return None;
+    }
+
+    match simulation.run_machine() {
+        MachineOutcome::Won => Some(Solution { placements }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
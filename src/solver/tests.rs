@@ -0,0 +1,44 @@
+use super::*;
+use crate::campaign::CAMPAIGN_LEVELS;
+
+#[test]
+fn first_steps_is_solved_with_the_move_up_rune_only() {
+    let solver_report = solve("levels/01_first_steps.level.ron");
+
+    // no runes, either rune in the single slot
+    assert_eq!(solver_report.rune_assignments_tried, 3);
+    assert_eq!(solver_report.min_runes(), Some(1));
+    assert_eq!(solver_report.solutions.len(), 1);
+    assert_eq!(
+        solver_report.solutions[0].placements,
+        [(
+            "move_up_rune".to_string(),
+            "ace_of_hearts_rune_slot".to_string()
+        )]
+    );
+}
+
+#[test]
+fn mvp_needs_both_runes() {
+    let solver_report = solve("levels/02_mvp.level.ron");
+
+    assert_eq!(solver_report.rune_assignments_tried, 7);
+    assert_eq!(solver_report.min_runes(), Some(2));
+    assert_eq!(solver_report.solutions.len(), 1);
+}
+
+/*
+Not a check, but the tool for level designers (see the top of solver.rs)
+ */
+#[test]
+#[ignore]
+fn solve_levels() {
+    let level_paths = match std::env::var("SOLVER_LEVEL") {
+        Ok(level_path) => vec![level_path],
+        Err(_) => CAMPAIGN_LEVELS.iter().map(ToString::to_string).collect(),
+    };
+
+    for level_path in level_paths {
+        println!("{}", solve(&level_path));
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BlueBall, GameState, MachineFailedEvent, MachineFailureReason, MachinePart, RuneEffect,
+    gate::Gate,
+    timing::{DelayNode, MetronomeNode},
+};
+
+/*
+========================================================================================
+Stall detection
+
+A running machine fails if
+- nothing has moved (and no rune effect is running, no gate or timing node is waiting) for a while
+- it has been running for too long
+- the blue ball or a machine part has left the play area
+-> the player is never stuck waiting for a machine that will not finish
+========================================================================================
+*/
+
+pub struct StallDetectionPlugin;
+
+impl Plugin for StallDetectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StallDetection>()
+            .init_resource::<MachineRunClock>()
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Building,
+                    entered: GameState::MachineRunning,
+                },
+                reset_machine_run_clock,
+            )
+            // note: on the fixed timestep, like the rest of the machine
+            .add_systems(
+                FixedUpdate,
+                detect_machine_stall.run_if(in_state(GameState::MachineRunning)),
+            );
+    }
+}
+
+/*
+Stall detection settings of the current level; set when a level is spawned
+ */
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct StallDetection {
+    // the machine fails if nothing has moved for this long
+    pub idle_seconds: f32,
+    // the machine fails after running this long, no matter what
+    pub timeout_seconds: f32,
+    // size of the play area, centered on the screen; anything leaving it fails the machine
+    pub play_area_size: Vec2,
+}
+
+impl Default for StallDetection {
+    fn default() -> Self {
+        StallDetection {
+            idle_seconds: 3.0,
+            timeout_seconds: 60.0,
+            // the screen (1280x720) plus some margin
+            play_area_size: Vec2::new(1480.0, 920.0),
+        }
+    }
+}
+
+/*
+Time since the machine was started and since something last moved
+ */
+#[derive(Resource, Default)]
+struct MachineRunClock {
+    run_seconds: f32,
+    idle_seconds: f32,
+}
+
+/*
+Starts the clock for a new run
+ */
+fn reset_machine_run_clock(
+    // Globals
+    mut machine_run_clock: ResMut<MachineRunClock>,
+) {
+    *machine_run_clock = MachineRunClock::default();
+}
+
+/*
+Fails the machine if it is stuck, runs too long or something has left the play area
+ */
+fn detect_machine_stall(
+    // Globals
+    time: Res<Time<Fixed>>,
+    stall_detection: Res<StallDetection>,
+    mut machine_run_clock: ResMut<MachineRunClock>,
+    mut machine_failed_event_writer: EventWriter<MachineFailedEvent>,
+    // Queries
+    moving_entities: Query<(), (Or<(With<MachinePart>, With<BlueBall>)>, Changed<Transform>)>,
+    // note: Transform instead of GlobalTransform, so parts that have just been restored count as back in the play area
+    tracked_entities: Query<(Entity, &Transform), Or<(With<MachinePart>, With<BlueBall>)>>,
+    gates: Query<&Gate>,
+    delay_nodes: Query<&DelayNode>,
+    metronome_nodes: Query<&MetronomeNode>,
+    rune_effects: Query<(Ref<RuneEffect>, &Transform)>,
+) {
+    machine_run_clock.run_seconds += time.delta_secs();
+
+    // note: an effect that has just been applied (e.g. an impulse) counts, even if nothing has moved yet
+    let rune_effect_running = rune_effects.iter().any(|(rune_effect, transform)| {
+        rune_effect.is_changed() || rune_effect.is_running(transform)
+    });
+
+    let waiting = rune_effect_running
+        || gates.iter().any(Gate::is_waiting)
+        || delay_nodes.iter().any(DelayNode::is_waiting)
+        || metronome_nodes.iter().any(MetronomeNode::is_waiting);
+
+    if moving_entities.is_empty() && !waiting {
+        machine_run_clock.idle_seconds += time.delta_secs();
+    } else {
+        machine_run_clock.idle_seconds = 0.0;
+    }
+
+    let play_area = Rect::from_center_size(Vec2::ZERO, stall_detection.play_area_size);
+
+    let left_play_area = tracked_entities
+        .iter()
+        .find(|(_, transform)| !play_area.contains(transform.translation.truncate()));
+
+    let reason = if let Some((entity, _)) = left_play_area {
+        MachineFailureReason::LeftPlayArea { entity }
+    } else if machine_run_clock.idle_seconds >= stall_detection.idle_seconds {
+        MachineFailureReason::Idle
+    } else if machine_run_clock.run_seconds >= stall_detection.timeout_seconds {
+        MachineFailureReason::Timeout
+    } else {
+        return;
+    };
+
+    machine_failed_event_writer.write(MachineFailedEvent { reason });
+
+    // only fail once; the clock starts again with the next run
+    *machine_run_clock = MachineRunClock::default();
+}
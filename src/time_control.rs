@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+
+/*
+========================================================================================
+Time controls
+
+While the machine is running, its speed can be changed:
+- 1 to 5 select 0.25x, 0.5x, 1x, 2x or 4x speed
+- Space freezes and unfreezes the machine
+- Period advances a frozen machine by a single physics step
+The whole machine (physics, rune effects, delays, stall detection) runs on physics steps, which follow virtual time
+-> scaling virtual time keeps everything in sync; the score counts machine time, so the speed doesn't matter for it
+The chosen speed is kept for the following runs, a frozen machine is unfrozen by the next run
+========================================================================================
+*/
+
+const MACHINE_SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+const DEFAULT_MACHINE_SPEED: usize = 2;
+
+const MACHINE_SPEED_KEYS: [KeyCode; 5] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+];
+
+pub struct TimeControlPlugin;
+
+impl Plugin for TimeControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MachineSpeed>()
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Building,
+                    entered: GameState::MachineRunning,
+                },
+                unfreeze_machine,
+            )
+            // note: also when resuming from pause, which unpauses time
+            .add_systems(OnEnter(GameState::MachineRunning), apply_machine_speed)
+            .add_systems(OnExit(GameState::MachineRunning), reset_time)
+            .add_systems(
+                Update,
+                (
+                    handle_time_control_keys,
+                    apply_machine_speed.run_if(resource_changed::<MachineSpeed>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::MachineRunning)),
+            )
+            .add_systems(
+                FixedPostUpdate,
+                finish_machine_step.run_if(in_state(GameState::MachineRunning)),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct MachineSpeed {
+    // index into MACHINE_SPEEDS
+    speed: usize,
+    frozen: bool,
+    // a frozen machine is running for a single physics step
+    stepping: bool,
+}
+
+impl Default for MachineSpeed {
+    fn default() -> Self {
+        MachineSpeed {
+            speed: DEFAULT_MACHINE_SPEED,
+            frozen: false,
+            stepping: false,
+        }
+    }
+}
+
+fn handle_time_control_keys(
+    // Globals
+    input: Res<ButtonInput<KeyCode>>,
+    mut machine_speed: ResMut<MachineSpeed>,
+) {
+    if let Some(speed) = MACHINE_SPEED_KEYS
+        .iter()
+        .position(|key| input.just_pressed(*key))
+    {
+        machine_speed.speed = speed;
+        info!("Machine speed: {}x", MACHINE_SPEEDS[speed]);
+    }
+
+    if input.just_pressed(KeyCode::Space) {
+        machine_speed.frozen = !machine_speed.frozen;
+        machine_speed.stepping = false;
+    }
+
+    if input.just_pressed(KeyCode::Period) && machine_speed.frozen {
+        machine_speed.stepping = true;
+    }
+}
+
+/*
+Sets virtual time to the speed of the machine
+ */
+fn apply_machine_speed(
+    // Globals
+    machine_speed: Res<MachineSpeed>,
+    time_fixed: Res<Time<Fixed>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let default_max_delta = Time::<Virtual>::default().max_delta();
+
+    if machine_speed.stepping {
+        // the next frame advances time by exactly one physics step (unless the frame is even shorter)
+        time.set_relative_speed(1.0);
+        time.set_max_delta(time_fixed.timestep());
+        time.unpause();
+        return;
+    }
+
+    time.set_relative_speed(MACHINE_SPEEDS[machine_speed.speed]);
+    time.set_max_delta(default_max_delta);
+
+    if machine_speed.frozen {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+}
+
+/*
+Freezes the machine again once the single step has been taken
+ */
+fn finish_machine_step(
+    // Globals
+    mut machine_speed: ResMut<MachineSpeed>,
+) {
+    if machine_speed.stepping {
+        machine_speed.stepping = false;
+    }
+}
+
+fn unfreeze_machine(
+    // Globals
+    mut machine_speed: ResMut<MachineSpeed>,
+) {
+    machine_speed.frozen = false;
+    machine_speed.stepping = false;
+}
+
+/*
+Building (and the screens after a run) always happen at normal speed
+ */
+fn reset_time(
+    // Globals
+    mut time: ResMut<Time<Virtual>>,
+) {
+    time.set_relative_speed(1.0);
+    time.set_max_delta(Time::<Virtual>::default().max_delta());
+    time.unpause();
+}
@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+
+use crate::{GameState, MAGICAL_BLUE, TriggerStarActivatedEvent};
+
+/*
+========================================================================================
+Timing nodes
+
+Delay: triggers its follow ups some time after it has been activated
+Metronome: triggers its follow ups again and again, either once activated or from the start of the machine
+-> timing puzzles, e.g. a rotating ring of sprinkles that has to be hit at the right moment
+Timing nodes are reset whenever the machine is started
+========================================================================================
+*/
+
+pub struct TimingPlugin;
+
+impl Plugin for TimingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnTransition {
+                exited: GameState::Building,
+                entered: GameState::MachineRunning,
+            },
+            reset_timing_nodes,
+        )
+        // note: on the fixed timestep, like the rest of the machine
+        .add_systems(
+            FixedUpdate,
+            (handle_event_timing_node_activated, tick_timing_nodes)
+                .chain()
+                .run_if(in_state(GameState::MachineRunning)),
+        );
+    }
+}
+
+#[derive(Component)]
+pub struct DelayNode {
+    delay_seconds: f32,
+    follow_up_entities: Vec<Entity>,
+    // started by the first activation; later activations during the same run are ignored
+    timer: Option<Timer>,
+}
+
+impl DelayNode {
+    pub fn new(delay_seconds: f32, follow_up_entities: Vec<Entity>) -> Self {
+        DelayNode {
+            delay_seconds,
+            follow_up_entities,
+            timer: None,
+        }
+    }
+
+    pub fn follow_up_entities(&self) -> &[Entity] {
+        &self.follow_up_entities
+    }
+
+    /*
+    Whether the delay has been started, but has not fired yet (the machine is not stuck)
+     */
+    pub fn is_waiting(&self) -> bool {
+        self.timer.as_ref().is_some_and(|timer| !timer.finished())
+    }
+}
+
+#[derive(Component)]
+pub struct MetronomeNode {
+    interval_seconds: f32,
+    // None -> ticks until the machine stops
+    repetitions: Option<u32>,
+    // starts ticking with the machine instead of waiting for an activation
+    autostart: bool,
+    follow_up_entities: Vec<Entity>,
+    timer: Option<Timer>,
+    // how often the follow ups have been triggered during this run
+    ticks: u32,
+}
+
+impl MetronomeNode {
+    pub fn new(
+        interval_seconds: f32,
+        repetitions: Option<u32>,
+        autostart: bool,
+        follow_up_entities: Vec<Entity>,
+    ) -> Self {
+        MetronomeNode {
+            interval_seconds,
+            repetitions,
+            autostart,
+            follow_up_entities,
+            timer: None,
+            ticks: 0,
+        }
+    }
+
+    pub fn follow_up_entities(&self) -> &[Entity] {
+        &self.follow_up_entities
+    }
+
+    /*
+    Whether the metronome is still ticking (the machine is not stuck)
+     */
+    pub fn is_waiting(&self) -> bool {
+        self.timer.as_ref().is_some_and(|timer| !timer.paused())
+    }
+
+    fn repetitions_done(&self) -> bool {
+        self.repetitions
+            .is_some_and(|repetitions| self.ticks >= repetitions)
+    }
+
+    fn start(&mut self) {
+        self.timer = Some(Timer::from_seconds(
+            self.interval_seconds,
+            TimerMode::Repeating,
+        ));
+    }
+}
+
+/*
+Stops all timers of the previous run; autostart metronomes start ticking
+ */
+fn reset_timing_nodes(
+    // Queries
+    delay_nodes: Query<(&mut DelayNode, Option<&mut Sprite>)>,
+    metronome_nodes: Query<(&mut MetronomeNode, Option<&mut Sprite>), Without<DelayNode>>,
+) {
+    for (mut delay_node, sprite) in delay_nodes {
+        delay_node.timer = None;
+
+        if let Some(mut sprite) = sprite {
+            sprite.color = Color::WHITE;
+        }
+    }
+
+    for (mut metronome_node, sprite) in metronome_nodes {
+        metronome_node.timer = None;
+        metronome_node.ticks = 0;
+
+        if metronome_node.autostart {
+            metronome_node.start();
+        }
+
+        if let Some(mut sprite) = sprite {
+            sprite.color = Color::WHITE;
+        }
+    }
+}
+
+/*
+Starts the timers of activated timing nodes
+ */
+fn handle_event_timing_node_activated(
+    // Globals
+    mut trigger_event_reader: EventReader<TriggerStarActivatedEvent>,
+    // Queries
+    mut delay_nodes: Query<&mut DelayNode>,
+    mut metronome_nodes: Query<&mut MetronomeNode>,
+) {
+    for trigger_event in trigger_event_reader.read() {
+        let entity = trigger_event.entity_to_be_triggered;
+
+        if let Ok(mut delay_node) = delay_nodes.get_mut(entity)
+            && delay_node.timer.is_none()
+        {
+            delay_node.timer = Some(Timer::from_seconds(
+                delay_node.delay_seconds,
+                TimerMode::Once,
+            ));
+            trace!("delay {} started", entity);
+        }
+
+        if let Ok(mut metronome_node) = metronome_nodes.get_mut(entity)
+            && metronome_node.timer.is_none()
+        {
+            metronome_node.start();
+            trace!("metronome {} started", entity);
+        }
+    }
+}
+
+/*
+Advances the timers and triggers the follow ups of timing nodes whose time has come
+ */
+fn tick_timing_nodes(
+    // Globals
+    time: Res<Time<Fixed>>,
+    mut trigger_event_writer: EventWriter<TriggerStarActivatedEvent>,
+    // Queries
+    delay_nodes: Query<(Entity, &mut DelayNode, Option<&mut Sprite>)>,
+    metronome_nodes: Query<(Entity, &mut MetronomeNode, Option<&mut Sprite>), Without<DelayNode>>,
+) {
+    for (delay_entity, mut delay_node, sprite) in delay_nodes {
+        let Some(timer) = &mut delay_node.timer else {
+            continue;
+        };
+
+        if !timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        trigger_event_writer.write_batch(delay_node.follow_up_entities.iter().map(
+            |follow_up_entity| TriggerStarActivatedEvent {
+                entity_to_be_triggered: *follow_up_entity,
+                triggered_by: delay_entity,
+            },
+        ));
+
+        if let Some(mut sprite) = sprite {
+            sprite.color = MAGICAL_BLUE;
+        }
+
+        debug!("delay {} fired", delay_entity);
+    }
+
+    for (metronome_entity, mut metronome_node, sprite) in metronome_nodes {
+        let Some(timer) = &mut metronome_node.timer else {
+            continue;
+        };
+
+        if timer.paused() {
+            continue;
+        }
+
+        // a long frame may contain several ticks
+        let times_finished = timer.tick(time.delta()).times_finished_this_tick();
+
+        for _ in 0..times_finished {
+            // e.g. no repetitions at all
+            if metronome_node.repetitions_done() {
+                break;
+            }
+
+            metronome_node.ticks += 1;
+
+            trigger_event_writer.write_batch(metronome_node.follow_up_entities.iter().map(
+                |follow_up_entity| TriggerStarActivatedEvent {
+                    entity_to_be_triggered: *follow_up_entity,
+                    triggered_by: metronome_entity,
+                },
+            ));
+
+            debug!(
+                "metronome {} ticked ({} times)",
+                metronome_entity, metronome_node.ticks
+            );
+        }
+
+        // all repetitions done; the timer stays paused for the rest of the run, so it no longer counts as waiting
+        if metronome_node.repetitions_done() {
+            metronome_node.timer.as_mut().unwrap().pause();
+        }
+
+        if times_finished > 0
+            && let Some(mut sprite) = sprite
+        {
+            sprite.color = MAGICAL_BLUE;
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::{BallFiringThingy, GameState, GreyBall, MAGICAL_BLUE, Rune, StarTrigger};
+
+/*
+========================================================================================
+Trajectory preview
+
+While building, a dotted line shows where a ball fired from each ball firing thingy will go
+The path is simulated step by step (gravity included, if the balls are affected by it) and ends
+at the first rune, trigger star or wall (any static body) the ball would hit
+Levels can turn the preview off (trajectory_preview: false) for harder puzzles
+========================================================================================
+*/
+
+// simulation step; same as the default fixed timestep
+const TRAJECTORY_STEP_SECONDS: f32 = 1.0 / 64.0;
+// the preview ends after this time, even if nothing was hit
+const TRAJECTORY_MAX_SECONDS: f32 = 8.0;
+// distance between two dots of the preview
+const TRAJECTORY_DOT_SPACING: f32 = 24.0;
+const TRAJECTORY_DOT_RADIUS: f32 = 3.0;
+
+const TRAJECTORY_DOT_BLUE: Color = Color::LinearRgba(LinearRgba::new(0.3, 0.3, 1.0, 0.6));
+
+pub struct TrajectoryPreviewPlugin;
+
+impl Plugin for TrajectoryPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrajectoryPreview>().add_systems(
+            Update,
+            draw_trajectory_preview.run_if(
+                in_state(GameState::Building)
+                    .and(resource_exists_and_equals(TrajectoryPreview { enabled: true })),
+            ),
+        );
+    }
+}
+
+/*
+Whether the current level shows the trajectory preview; set when a level is spawned
+ */
+#[derive(Resource, PartialEq)]
+pub struct TrajectoryPreview {
+    pub enabled: bool,
+}
+
+impl Default for TrajectoryPreview {
+    fn default() -> Self {
+        TrajectoryPreview { enabled: true }
+    }
+}
+
+/*
+Simulates and draws the path of a ball fired from every ball firing thingy
+ */
+fn draw_trajectory_preview(
+    // Globals
+    mut gizmos: Gizmos,
+    spatial_query: SpatialQuery,
+    gravity: Res<Gravity>,
+    // Queries
+    ball_firing_thingies: Query<(&BallFiringThingy, &Transform)>,
+    grey_balls: Query<(&Collider, &RigidBody, Option<&GravityScale>), With<GreyBall>>,
+    runes: Query<(), With<Rune>>,
+    star_triggers: Query<(), With<StarTrigger>>,
+    rigid_bodies: Query<&RigidBody>,
+) {
+    // all grey balls are the same, so any of them will do for the shape
+    let Some((ball_collider, ball_rigid_body, ball_gravity_scale)) = grey_balls.iter().next()
+    else {
+        return;
+    };
+
+    // kinematic balls (the default) fly in a straight line
+    let ball_gravity = if ball_rigid_body.is_dynamic() {
+        gravity.0 * ball_gravity_scale.map_or(1.0, |gravity_scale| gravity_scale.0)
+    } else {
+        Vec2::ZERO
+    };
+
+    let stops_ball = |entity: Entity| {
+        runes.contains(entity)
+            || star_triggers.contains(entity)
+            || rigid_bodies
+                .get(entity)
+                .is_ok_and(|rigid_body| rigid_body.is_static())
+    };
+
+    for (ball_firing_thingy, transform) in ball_firing_thingies {
+        let mut position = transform.translation.truncate();
+        let mut velocity = ball_firing_thingy.firing_velocity(transform);
+
+        // start with a dot right at the thingy
+        let mut distance_to_next_dot = 0.0;
+
+        let mut elapsed_seconds = 0.0;
+
+        while elapsed_seconds < TRAJECTORY_MAX_SECONDS {
+            velocity += ball_gravity * TRAJECTORY_STEP_SECONDS;
+
+            let step = velocity * TRAJECTORY_STEP_SECONDS;
+
+            let Ok(direction) = Dir2::new(step) else {
+                // ball is standing still, nothing more to show
+                break;
+            };
+
+            let hit = spatial_query.cast_shape_predicate(
+                ball_collider,
+                position,
+                0.0,
+                direction,
+                &ShapeCastConfig::from_max_distance(step.length()),
+                &SpatialQueryFilter::default(),
+                &stops_ball,
+            );
+
+            let step_length = hit.map_or(step.length(), |hit| hit.distance);
+
+            // place dots along this step
+            let mut travelled = distance_to_next_dot;
+            while travelled <= step_length {
+                gizmos.circle_2d(
+                    position + direction * travelled,
+                    TRAJECTORY_DOT_RADIUS,
+                    TRAJECTORY_DOT_BLUE,
+                );
+                travelled += TRAJECTORY_DOT_SPACING;
+            }
+            distance_to_next_dot = travelled - step_length;
+
+            position += direction * step_length;
+
+            if hit.is_some() {
+                // mark where the ball hits something
+                gizmos.circle_2d(position, TRAJECTORY_DOT_RADIUS * 2.0, MAGICAL_BLUE);
+                break;
+            }
+
+            elapsed_seconds += TRAJECTORY_STEP_SECONDS;
+        }
+    }
+}
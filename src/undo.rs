@@ -0,0 +1,376 @@
+use bevy::prelude::*;
+
+use crate::{
+    GameState, MAGICAL_BLUE, Picked, Placed, Rune, RuneSlot, level::CurrentLevel,
+    replay::ReplayPlayback,
+};
+
+/*
+========================================================================================
+Undo / redo
+
+Every build action (placing a rune in a slot, replacing a rune, removing a rune from its slot) is recorded
+as the rune layout before and after the action; undo and redo switch between those layouts
+-> Ctrl+Z / Ctrl+Y (or Ctrl+Shift+Z), or the two arrow buttons in the top right corner
+Launching a ball starts the machine, which puts all runes back afterwards -> the history starts over
+Balls are not part of the history: a ball dropped anywhere but on a ball firing thingy goes straight back to its place
+(nothing changes), a ball dropped on one is launched (see above) -> placing a ball can't be undone, only the runes
+========================================================================================
+*/
+
+const UNDO_BUTTON_IMAGE: &str = "UI Pack/PNG/Grey/Double/arrow_decorative_w.png";
+const REDO_BUTTON_IMAGE: &str = "UI Pack/PNG/Blue/Double/arrow_decorative_e.png";
+
+const HISTORY_BUTTON_SIZE: f32 = 48.0;
+
+const UNAVAILABLE_GREY: Color = Color::LinearRgba(LinearRgba::rgb(0.3, 0.3, 0.3));
+
+pub struct UndoPlugin;
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BuildHistory>()
+            .add_event::<BuildHistoryEvent>()
+            // note: spawned once per level (Building is entered after every run); the editor has no build history
+            .add_systems(
+                OnEnter(GameState::Building),
+                show_history_buttons.run_if(not(any_with_component::<HistoryButtons>)),
+            )
+            .add_systems(OnEnter(GameState::MainMenu), hide_history_buttons)
+            .add_systems(OnEnter(GameState::Editing), hide_history_buttons)
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Building,
+                    entered: GameState::MachineRunning,
+                },
+                clear_build_history,
+            )
+            // a level starts with an empty history (e.g. after leaving a test run of the editor mid-build)
+            .add_systems(
+                Update,
+                clear_build_history.run_if(resource_exists_and_changed::<CurrentLevel>),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        (handle_history_keys, handle_history_buttons)
+                            .run_if(not(resource_exists::<ReplayPlayback>)),
+                        handle_event_build_history.run_if(on_event::<BuildHistoryEvent>),
+                        record_build_actions,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Building)),
+                    update_history_buttons,
+                ),
+            );
+    }
+}
+
+/*
+Where a rune is and what it affects
+ */
+#[derive(Clone, PartialEq)]
+struct RunePlacement {
+    rune: Entity,
+    rune_slot: Option<Entity>,
+    transform: Transform,
+    affected_entity: Option<Entity>,
+}
+
+/*
+A single build action, as the layout of all runes before and after it
+ */
+struct BuildAction {
+    before: Vec<RunePlacement>,
+    after: Vec<RunePlacement>,
+}
+
+#[derive(Resource, Default)]
+struct BuildHistory {
+    undo_stack: Vec<BuildAction>,
+    redo_stack: Vec<BuildAction>,
+    // layout after the last recorded action (or undo/redo); None -> the next layout is the start of a new history
+    settled_layout: Option<Vec<RunePlacement>>,
+}
+
+#[derive(Event, Clone, Copy)]
+pub enum BuildHistoryEvent {
+    Undo,
+    Redo,
+}
+
+/*
+Marks the root UI node of the undo and redo buttons
+ */
+#[derive(Component)]
+struct HistoryButtons;
+
+#[derive(Component, Clone, Copy)]
+struct HistoryButton {
+    build_history_event: BuildHistoryEvent,
+}
+
+/*
+Spawns the undo and redo buttons in the top right corner once a level is started
+ */
+fn show_history_buttons(
+    // Globals
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands
+        .spawn((
+            HistoryButtons,
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(16.0),
+                top: Val::Px(16.0),
+                column_gap: Val::Px(8.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for (image, build_history_event) in [
+                (UNDO_BUTTON_IMAGE, BuildHistoryEvent::Undo),
+                (REDO_BUTTON_IMAGE, BuildHistoryEvent::Redo),
+            ] {
+                parent.spawn((
+                    HistoryButton {
+                        build_history_event,
+                    },
+                    Button,
+                    ImageNode::new(asset_server.load(image)).with_color(UNAVAILABLE_GREY),
+                    Node {
+                        width: Val::Px(HISTORY_BUTTON_SIZE),
+                        height: Val::Px(HISTORY_BUTTON_SIZE),
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+/*
+Removes the undo and redo buttons when the player returns to the level select screen
+ */
+fn hide_history_buttons(
+    // Globals
+    mut commands: Commands,
+    // Queries
+    history_buttons: Query<Entity, With<HistoryButtons>>,
+) {
+    for history_buttons in history_buttons {
+        commands.entity(history_buttons).despawn();
+    }
+}
+
+/*
+Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes
+ */
+fn handle_history_keys(
+    // Globals
+    input: Res<ButtonInput<KeyCode>>,
+    mut build_history_event_writer: EventWriter<BuildHistoryEvent>,
+) {
+    if !input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    let shift = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if input.just_pressed(KeyCode::KeyY) || (shift && input.just_pressed(KeyCode::KeyZ)) {
+        build_history_event_writer.write(BuildHistoryEvent::Redo);
+    } else if input.just_pressed(KeyCode::KeyZ) {
+        build_history_event_writer.write(BuildHistoryEvent::Undo);
+    }
+}
+
+fn handle_history_buttons(
+    // Globals
+    mut build_history_event_writer: EventWriter<BuildHistoryEvent>,
+    // Queries
+    history_buttons: Query<(&Interaction, &HistoryButton), Changed<Interaction>>,
+) {
+    for (interaction, history_button) in history_buttons {
+        if *interaction == Interaction::Pressed {
+            build_history_event_writer.write(history_button.build_history_event);
+        }
+    }
+}
+
+/*
+Puts the runes back into the layout before (undo) or after (redo) the most recent action
+ */
+fn handle_event_build_history(
+    // Globals
+    mut commands: Commands,
+    mut build_history: ResMut<BuildHistory>,
+    mut build_history_event_reader: EventReader<BuildHistoryEvent>,
+    // Queries
+    held: Query<(), Or<(With<Picked>, With<Placed>)>>,
+    mut runes: Query<(&mut Rune, &mut Transform)>,
+) {
+    // note: all events are read, so they don't pile up while something is held
+    let build_history_events: Vec<BuildHistoryEvent> =
+        build_history_event_reader.read().copied().collect();
+
+    // an action is still in progress
+    if !held.is_empty() {
+        return;
+    }
+
+    for build_history_event in build_history_events {
+        let layout = match build_history_event {
+            BuildHistoryEvent::Undo => {
+                let Some(build_action) = build_history.undo_stack.pop() else {
+                    continue;
+                };
+                let layout = build_action.before.clone();
+                build_history.redo_stack.push(build_action);
+                layout
+            }
+            BuildHistoryEvent::Redo => {
+                let Some(build_action) = build_history.redo_stack.pop() else {
+                    continue;
+                };
+                let layout = build_action.after.clone();
+                build_history.undo_stack.push(build_action);
+                layout
+            }
+        };
+
+        for rune_placement in &layout {
+            let Ok((mut rune, mut transform)) = runes.get_mut(rune_placement.rune) else {
+                continue;
+            };
+
+            *transform = rune_placement.transform;
+            rune.affected_entity = rune_placement.affected_entity;
+
+            match rune_placement.rune_slot {
+                Some(rune_slot) => {
+                    commands.entity(rune_slot).add_child(rune_placement.rune);
+                }
+                None => {
+                    commands.entity(rune_placement.rune).remove::<ChildOf>();
+                }
+            }
+        }
+
+        // the restored layout is not a new action
+        build_history.settled_layout = Some(layout);
+    }
+}
+
+/*
+Records an action whenever the rune layout has changed and nothing is held anymore
+ */
+fn record_build_actions(
+    // Globals
+    mut build_history: ResMut<BuildHistory>,
+    // Queries
+    held: Query<(), Or<(With<Picked>, With<Placed>)>>,
+    runes: Query<(Entity, &Rune, &Transform, Option<&ChildOf>)>,
+    rune_slots: Query<(), With<RuneSlot>>,
+) {
+    // an action is still in progress
+    if !held.is_empty() {
+        return;
+    }
+
+    let mut layout: Vec<RunePlacement> = runes
+        .iter()
+        .map(|(rune_entity, rune, transform, child_of)| RunePlacement {
+            rune: rune_entity,
+            rune_slot: child_of
+                .map(ChildOf::parent)
+                .filter(|parent| rune_slots.contains(*parent)),
+            transform: *transform,
+            affected_entity: rune.affected_entity,
+        })
+        .collect();
+    layout.sort_by_key(|rune_placement| rune_placement.rune);
+
+    let Some(settled_layout) = &build_history.settled_layout else {
+        build_history.settled_layout = Some(layout);
+        return;
+    };
+
+    if *settled_layout == layout {
+        return;
+    }
+
+    // different runes -> a different level has been started
+    let same_runes = settled_layout.len() == layout.len()
+        && settled_layout
+            .iter()
+            .zip(&layout)
+            .all(|(settled, current)| settled.rune == current.rune);
+
+    if !same_runes {
+        *build_history = BuildHistory {
+            settled_layout: Some(layout),
+            ..default()
+        };
+        return;
+    }
+
+    let before = build_history
+        .settled_layout
+        .replace(layout.clone())
+        .unwrap();
+
+    build_history.undo_stack.push(BuildAction {
+        before,
+        after: layout,
+    });
+    // a new action makes the undone ones obsolete
+    build_history.redo_stack.clear();
+
+    debug!(
+        "build action recorded ({} in history)",
+        build_history.undo_stack.len()
+    );
+}
+
+/*
+The machine resets all runes after the run, so the history would no longer match
+ */
+fn clear_build_history(
+    // Globals
+    mut build_history: ResMut<BuildHistory>,
+) {
+    *build_history = BuildHistory::default();
+}
+
+/*
+Greys out the buttons while there is nothing to undo or redo
+ */
+fn update_history_buttons(
+    // Globals
+    game_state: Res<State<GameState>>,
+    build_history: Res<BuildHistory>,
+    // Queries
+    history_buttons: Query<(&HistoryButton, &mut ImageNode)>,
+) {
+    let building = *game_state.get() == GameState::Building;
+
+    for (history_button, mut image_node) in history_buttons {
+        let available = building
+            && match history_button.build_history_event {
+                BuildHistoryEvent::Undo => !build_history.undo_stack.is_empty(),
+                BuildHistoryEvent::Redo => !build_history.redo_stack.is_empty(),
+            };
+
+        let button_color = if available {
+            MAGICAL_BLUE
+        } else {
+            UNAVAILABLE_GREY
+        };
+
+        if image_node.color != button_color {
+            image_node.color = button_color;
+        }
+    }
+}